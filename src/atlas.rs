@@ -0,0 +1,651 @@
+//! Atlas descriptors naming the sprites packed into a texture.
+//!
+//! Descriptors are either JSON arrays of sprites like
+//! `[{"name": "hero/idle", "x": 0, "y": 0, "w": 32, "h": 48}]`, or property lists
+//! written by TexturePacker for cocos2d (formats 1 to 3), whose frames can be
+//! rotated and trimmed.
+
+use std::path::Path;
+
+use crate::{transform::Rect, Failure};
+
+/// A named region of an atlas texture.
+pub struct Sprite {
+    pub name: String,
+    /// Region of the texture holding the sprite, as stored.
+    pub rect: Rect,
+    /// Whether the sprite is stored rotated a quarter turn clockwise, so `rect` is
+    /// as wide as the sprite is high.
+    pub rotated: bool,
+    /// Where the stored pixels go on the untrimmed sprite, for sprites whose
+    /// transparent edges were trimmed away: `x` and `y` are the offset and `width`
+    /// and `height` the original size.
+    pub trim: Option<Rect>,
+}
+
+/// The subset of JSON and property list values atlas descriptors use.
+enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Bool(_) => "a boolean",
+            Value::Number(_) => "a number",
+            Value::String(_) => "a string",
+            Value::Array(_) => "an array",
+            Value::Object(_) => "an object",
+        }
+    }
+}
+
+/// Deepest nesting of arrays and objects either parser accepts, which keeps
+/// malformed descriptors from overflowing the stack.
+const MAX_DEPTH: usize = 128;
+
+/// Recursive descent parser over the bytes of a JSON document.
+struct Parser<'a> {
+    json: &'a [u8],
+    position: usize,
+    /// Number of values the current one is nested in.
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("{message} at byte {}", self.position)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .json
+            .get(self.position)
+            .is_some_and(|byte| byte.is_ascii_whitespace())
+        {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.json.get(self.position).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        if self.peek() != Some(byte) {
+            return Err(self.error(&format!("expected '{}'", byte as char)));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn literal(&mut self, word: &str, value: Value) -> Result<Value, String> {
+        if !self.json[self.position..].starts_with(word.as_bytes()) {
+            return Err(self.error("invalid literal"));
+        }
+        self.position += word.len();
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("values nested too deeply"));
+        }
+        self.depth += 1;
+        let value = self.unnested_value();
+        self.depth -= 1;
+        value
+    }
+
+    fn unnested_value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => Ok(Value::String(self.string()?)),
+            Some(b't') => self.literal("true", Value::Bool(true)),
+            Some(b'f') => self.literal("false", Value::Bool(false)),
+            Some(b'n') => self.literal("null", Value::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        self.expect(b'{')?;
+        let mut members = Vec::new();
+        if self.peek() == Some(b'}') {
+            self.position += 1;
+            return Ok(Value::Object(members));
+        }
+        loop {
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected a member name"));
+            }
+            let name = self.string()?;
+            self.expect(b':')?;
+            members.push((name, self.value()?));
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b'}') => {
+                    self.position += 1;
+                    return Ok(Value::Object(members));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect(b'[')?;
+        let mut elements = Vec::new();
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(Value::Array(elements));
+        }
+        loop {
+            elements.push(self.value()?);
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(Value::Array(elements));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.position;
+        while self
+            .json
+            .get(self.position)
+            .is_some_and(|byte| matches!(byte, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'))
+        {
+            self.position += 1;
+        }
+        std::str::from_utf8(&self.json[start..self.position])
+            .ok()
+            .and_then(|number| number.parse().ok())
+            .map(Value::Number)
+            .ok_or_else(|| self.error("invalid number"))
+    }
+
+    fn hex_escape(&mut self) -> Result<u32, String> {
+        let digits = self
+            .json
+            .get(self.position..self.position + 4)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        self.position += 4;
+        Ok(digits)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let mut bytes = Vec::new();
+        loop {
+            let Some(&byte) = self.json.get(self.position) else {
+                return Err(self.error("unterminated string"));
+            };
+            self.position += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escape = self.json.get(self.position).copied();
+                    self.position += 1;
+                    let character = match escape {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            let mut code = self.hex_escape()?;
+                            // Characters outside of the basic plane are escaped as
+                            // a pair of surrogates.
+                            if (0xD800..0xDC00).contains(&code)
+                                && self.json[self.position..].starts_with(b"\\u")
+                            {
+                                self.position += 2;
+                                let low = self.hex_escape()?;
+                                if !(0xDC00..0xE000).contains(&low) {
+                                    return Err(self.error("invalid surrogate pair"));
+                                }
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                            }
+                            char::from_u32(code)
+                                .ok_or_else(|| self.error("invalid unicode escape"))?
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    bytes.extend_from_slice(character.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                _ => bytes.push(byte),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| self.error("invalid UTF-8 in string"))
+    }
+}
+
+fn parse_json(json: &[u8]) -> Result<Value, String> {
+    let mut parser = Parser {
+        json,
+        position: 0,
+        depth: 0,
+    };
+    let value = parser.value()?;
+    if parser.peek().is_some() {
+        return Err(parser.error("trailing characters"));
+    }
+    Ok(value)
+}
+
+/// Reads the sprite at `index` from a JSON object with `name`, `x`, `y`, `w` and `h`.
+fn sprite(index: usize, value: &Value) -> Result<Sprite, String> {
+    let Value::Object(members) = value else {
+        return Err(format!(
+            "sprite {index} is {} instead of an object",
+            value.kind()
+        ));
+    };
+    let member = |name: &str| {
+        members
+            .iter()
+            .find(|(member, _)| member == name)
+            .map(|(_, value)| value)
+            .ok_or_else(|| format!("sprite {index} has no \"{name}\""))
+    };
+    let coordinate = |name: &str| match member(name)? {
+        &Value::Number(number)
+            if number >= 0.0 && number.fract() == 0.0 && number <= u32::MAX as f64 =>
+        {
+            Ok(number as u32)
+        }
+        value => Err(format!(
+            "\"{name}\" of sprite {index} is {} instead of a non-negative integer",
+            value.kind()
+        )),
+    };
+
+    let name = match member("name")? {
+        Value::String(name) => name.clone(),
+        value => return Err(format!("\"name\" of sprite {index} is {}", value.kind())),
+    };
+    Ok(Sprite {
+        name,
+        rect: Rect {
+            x: coordinate("x")?,
+            y: coordinate("y")?,
+            width: coordinate("w")?,
+            height: coordinate("h")?,
+        },
+        rotated: false,
+        trim: None,
+    })
+}
+
+/// Parser over the bytes of an XML property list, which knows just enough XML to
+/// read the elements property lists consist of.
+struct PlistParser<'a> {
+    xml: &'a [u8],
+    position: usize,
+    /// Number of elements the current one is nested in.
+    depth: usize,
+}
+
+impl PlistParser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("{message} at byte {}", self.position)
+    }
+
+    fn rest(&self) -> &[u8] {
+        &self.xml[self.position..]
+    }
+
+    /// Skips to just after the next occurrence of `end`.
+    fn skip_past(&mut self, end: &str) -> Result<(), String> {
+        let offset = self
+            .rest()
+            .windows(end.len())
+            .position(|window| window == end.as_bytes())
+            .ok_or_else(|| self.error(&format!("expected \"{end}\"")))?;
+        self.position += offset + end.len();
+        Ok(())
+    }
+
+    /// Skips whitespace, comments and the XML declaration and doctype.
+    fn skip_misc(&mut self) -> Result<(), String> {
+        loop {
+            while self.rest().first().is_some_and(u8::is_ascii_whitespace) {
+                self.position += 1;
+            }
+            if self.rest().starts_with(b"<!--") {
+                self.skip_past("-->")?;
+            } else if self.rest().starts_with(b"<?") {
+                self.skip_past("?>")?;
+            } else if self.rest().starts_with(b"<!") {
+                self.skip_past(">")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Reads a start tag, returning its name and whether it closes itself.
+    fn start_tag(&mut self) -> Result<(String, bool), String> {
+        self.skip_misc()?;
+        if self.rest().first() != Some(&b'<') || self.rest().get(1) == Some(&b'/') {
+            return Err(self.error("expected an element"));
+        }
+        let start = self.position + 1;
+        self.skip_past(">")?;
+        let tag = &self.xml[start..self.position - 1];
+        let self_closing = tag.ends_with(b"/");
+        let name = tag
+            .split(|byte| byte.is_ascii_whitespace() || *byte == b'/')
+            .next()
+            .unwrap_or_default();
+        Ok((String::from_utf8_lossy(name).into_owned(), self_closing))
+    }
+
+    /// Checks whether the end tag of `name` follows, consuming it if so.
+    fn end_tag(&mut self, name: &str) -> Result<bool, String> {
+        self.skip_misc()?;
+        let tag = format!("</{name}>");
+        if !self.rest().starts_with(tag.as_bytes()) {
+            return Ok(false);
+        }
+        self.position += tag.len();
+        Ok(true)
+    }
+
+    /// Reads the text up to the end tag of `name`, replacing entity references.
+    fn text(&mut self, name: &str) -> Result<String, String> {
+        let start = self.position;
+        self.skip_past(&format!("</{name}>"))?;
+        let raw = std::str::from_utf8(&self.xml[start..self.position - name.len() - 3])
+            .map_err(|_| self.error("invalid UTF-8 in text"))?;
+        let mut text = String::with_capacity(raw.len());
+        let mut rest = raw;
+        while let Some(ampersand) = rest.find('&') {
+            text.push_str(&rest[..ampersand]);
+            let semicolon = rest[ampersand..]
+                .find(';')
+                .ok_or_else(|| self.error("unterminated entity reference"))?;
+            let entity = &rest[ampersand + 1..ampersand + semicolon];
+            let character = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ => entity
+                    .strip_prefix("#x")
+                    .map(|hex| u32::from_str_radix(hex, 16))
+                    .or_else(|| entity.strip_prefix('#').map(str::parse))
+                    .and_then(Result::ok)
+                    .and_then(char::from_u32),
+            };
+            text.push(character.ok_or_else(|| self.error("invalid entity reference"))?);
+            rest = &rest[ampersand + semicolon + 1..];
+        }
+        text.push_str(rest);
+        Ok(text)
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("elements nested too deeply"));
+        }
+        self.depth += 1;
+        let value = self.unnested_value();
+        self.depth -= 1;
+        value
+    }
+
+    fn unnested_value(&mut self) -> Result<Value, String> {
+        let (name, self_closing) = self.start_tag()?;
+        let text = |parser: &mut Self| match self_closing {
+            true => Ok(String::new()),
+            false => parser.text(&name),
+        };
+        match name.as_str() {
+            "dict" => {
+                let mut members = Vec::new();
+                while !self_closing && !self.end_tag("dict")? {
+                    match self.start_tag()? {
+                        (key, false) if key == "key" => {
+                            let key = self.text("key")?;
+                            members.push((key, self.value()?));
+                        }
+                        _ => return Err(self.error("expected a key")),
+                    }
+                }
+                Ok(Value::Object(members))
+            }
+            "array" => {
+                let mut elements = Vec::new();
+                while !self_closing && !self.end_tag("array")? {
+                    elements.push(self.value()?);
+                }
+                Ok(Value::Array(elements))
+            }
+            "string" | "data" | "date" => Ok(Value::String(text(self)?)),
+            "integer" | "real" => text(self)?
+                .trim()
+                .parse()
+                .map(Value::Number)
+                .map_err(|_| self.error("invalid number")),
+            "true" | "false" => {
+                text(self)?;
+                Ok(Value::Bool(name == "true"))
+            }
+            _ => Err(self.error(&format!("unexpected element <{name}>"))),
+        }
+    }
+}
+
+fn parse_plist(xml: &[u8]) -> Result<Value, String> {
+    let mut parser = PlistParser {
+        xml,
+        position: 0,
+        depth: 0,
+    };
+    match parser.start_tag()? {
+        (name, false) if name == "plist" => {}
+        _ => return Err(parser.error("expected <plist>")),
+    }
+    let value = parser.value()?;
+    if !parser.end_tag("plist")? {
+        return Err(parser.error("expected </plist>"));
+    }
+    parser.skip_misc()?;
+    if !parser.rest().is_empty() {
+        return Err(parser.error("trailing characters"));
+    }
+    Ok(value)
+}
+
+/// Reads the numbers of a cocos2d geometry string like `{{0,0},{32,48}}`.
+fn geometry<const N: usize>(value: &str) -> Option<[f64; N]> {
+    let numbers = value
+        .split(',')
+        .map(|number| number.trim_matches(|c: char| c == '{' || c == '}' || c.is_whitespace()))
+        .map(|number| number.parse().ok())
+        .collect::<Option<Vec<f64>>>()?;
+    numbers.try_into().ok()
+}
+
+/// Reads the frame called `name` from the `frames` of a TexturePacker property list.
+fn plist_sprite(name: &str, value: &Value) -> Result<Sprite, String> {
+    let Value::Object(members) = value else {
+        return Err(format!(
+            "frame {name} is {} instead of a dict",
+            value.kind()
+        ));
+    };
+    let member = |names: &[&str]| {
+        members
+            .iter()
+            .find(|(member, _)| names.contains(&member.as_str()))
+            .map(|(_, value)| value)
+    };
+    let numbers = |names: &[&str]| -> Result<Option<Vec<f64>>, String> {
+        let Some(value) = member(names) else {
+            return Ok(None);
+        };
+        let invalid = || format!("\"{}\" of frame {name} is not a valid geometry", names[0]);
+        let Value::String(value) = value else {
+            return Err(invalid());
+        };
+        let numbers = match geometry::<4>(value) {
+            Some(numbers) => numbers.to_vec(),
+            None => geometry::<2>(value).ok_or_else(invalid)?.to_vec(),
+        };
+        Ok(Some(numbers))
+    };
+    let coordinates = |names: &[&str], count: usize| -> Result<Option<Vec<u32>>, String> {
+        let Some(numbers) = numbers(names)? else {
+            return Ok(None);
+        };
+        if numbers.len() != count
+            || !numbers
+                .iter()
+                .all(|&number| number >= 0.0 && number.fract() == 0.0 && number <= u32::MAX as f64)
+        {
+            return Err(format!(
+                "\"{}\" of frame {name} is not {count} non-negative integers",
+                names[0]
+            ));
+        }
+        Ok(Some(numbers.iter().map(|&number| number as u32).collect()))
+    };
+
+    let Some(frame) = coordinates(&["frame", "textureRect"], 4)? else {
+        return Err(format!("frame {name} has no \"frame\""));
+    };
+    let rotated = match member(&["rotated", "textureRotated"]) {
+        None => false,
+        Some(&Value::Bool(rotated)) => rotated,
+        Some(value) => {
+            return Err(format!(
+                "\"rotated\" of frame {name} is {} instead of a boolean",
+                value.kind()
+            ))
+        }
+    };
+    let [x, y, width, height] = frame[..] else {
+        unreachable!()
+    };
+
+    // Formats 1 and 2 store where the frame was cut from the original sprite,
+    // format 3 its offset from the center with y pointing up.
+    let trim = match (
+        coordinates(&["sourceColorRect"], 4)?,
+        coordinates(&["sourceSize", "spriteSourceSize"], 2)?,
+        numbers(&["spriteOffset", "offset"])?,
+    ) {
+        (Some(source_rect), Some(source_size), _) => Some(Rect {
+            x: source_rect[0],
+            y: source_rect[1],
+            width: source_size[0],
+            height: source_size[1],
+        }),
+        (None, Some(source_size), Some(offset)) if offset.len() == 2 => {
+            let centered = |source: u32, size: u32, offset: f64| {
+                ((source as f64 - size as f64) / 2.0 + offset)
+                    .round()
+                    .max(0.0) as u32
+            };
+            Some(Rect {
+                x: centered(source_size[0], width, offset[0]),
+                y: centered(source_size[1], height, -offset[1]),
+                width: source_size[0],
+                height: source_size[1],
+            })
+        }
+        _ => None,
+    };
+    if let Some(trim) = trim {
+        if trim.x as u64 + width as u64 > trim.width as u64
+            || trim.y as u64 + height as u64 > trim.height as u64
+        {
+            return Err(format!(
+                "trimmed frame {name} of {width}x{height} doesn't fit within its source size of {}x{}",
+                trim.width, trim.height
+            ));
+        }
+    }
+
+    // Frames are usually named after the image they were packed from.
+    let name = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && !extension.contains('/') => stem,
+        _ => name,
+    };
+    let (stored_width, stored_height) = if rotated {
+        (height, width)
+    } else {
+        (width, height)
+    };
+    Ok(Sprite {
+        name: name.to_string(),
+        rect: Rect {
+            x,
+            y,
+            width: stored_width,
+            height: stored_height,
+        },
+        rotated,
+        trim,
+    })
+}
+
+/// Reads the sprites of a TexturePacker property list.
+fn plist_sprites(plist: &Value) -> Result<Vec<Sprite>, String> {
+    let frames = match plist {
+        Value::Object(members) => members
+            .iter()
+            .find(|(member, _)| member == "frames")
+            .map(|(_, frames)| frames),
+        _ => None,
+    };
+    let Some(Value::Object(frames)) = frames else {
+        return Err("expected a dict with a \"frames\" dict".to_string());
+    };
+    frames
+        .iter()
+        .map(|(name, frame)| plist_sprite(name, frame))
+        .collect()
+}
+
+/// Reads the sprites of the atlas descriptor at `path`.
+pub fn read(path: &Path) -> Result<Vec<Sprite>, (Failure, String)> {
+    let descriptor = std::fs::read(path).map_err(|error| (Failure::Io, error.to_string()))?;
+    let malformed = |error| (Failure::Malformed, error);
+    if descriptor.trim_ascii_start().starts_with(b"<") {
+        return parse_plist(&descriptor)
+            .and_then(|plist| plist_sprites(&plist))
+            .map_err(malformed);
+    }
+    let Value::Array(sprites) = parse_json(&descriptor).map_err(malformed)? else {
+        return Err(malformed("expected an array of sprites".to_string()));
+    };
+    sprites
+        .iter()
+        .enumerate()
+        .map(|(index, value)| sprite(index, value).map_err(malformed))
+        .collect()
+}
@@ -0,0 +1,155 @@
+#[cfg(feature = "count-allocations")]
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::{
+    path::PathBuf,
+    process::ExitCode,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    decode_pixels, mip_levels, parse_texture, take_pixels, DecodeOptions, Failure, UnknownFormat,
+    DECODE_THREADS,
+};
+
+#[derive(clap::Parser)]
+pub struct Bench {
+    tex_path: PathBuf,
+    /// Number of times to decode the texture
+    #[clap(long, default_value_t = 100)]
+    iterations: usize,
+    /// Number of threads to decode the texture on, 0 uses one thread per CPU core
+    ///
+    /// Textures smaller than 512x512 are always decoded on a single thread.
+    #[clap(long, default_value_t = 1)]
+    threads: usize,
+    #[clap(flatten)]
+    decode_options: DecodeOptions,
+}
+
+/// System allocator that counts allocations, so the benchmark can report how
+/// many decoding needs.
+#[cfg(feature = "count-allocations")]
+struct CountingAllocator;
+
+#[cfg(feature = "count-allocations")]
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Number of allocations made so far, which stays 0 unless allocations are counted.
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(feature = "count-allocations")]
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+/// Repeatedly decodes the base level of a texture and reports how long that took,
+/// excluding reading the file and encoding the result.
+///
+/// Formats which are converted in place get decoded from their own output on every
+/// other iteration, which doesn't change the amount of work done. The decoded buffer
+/// is reused between iterations like it is between files of a batch conversion.
+pub fn bench(args: &Bench) -> ExitCode {
+    let mut tex = match std::fs::read(&args.tex_path) {
+        Ok(tex) => tex,
+        Err(error) => {
+            eprintln!("Failed to read {}: {error}", args.tex_path.display());
+            return Failure::Io.into();
+        }
+    };
+
+    let (header, format) = match parse_texture(&tex, UnknownFormat::Reject, false) {
+        Ok(parsed) => parsed,
+        Err(failure) => return failure.into(),
+    };
+    let levels = mip_levels(
+        format,
+        header.width as usize,
+        header.height as usize,
+        (header.mipmaps as usize).max(1),
+    );
+    let level = levels[0];
+    let pixels = match header.pixels_range(tex.len()) {
+        Ok(range) => &mut tex[range],
+        Err(error) => {
+            eprintln!("Invalid texture: {error}");
+            return Failure::Malformed.into();
+        }
+    };
+    let Some(pixels) = take_pixels(pixels, &levels, 0) else {
+        return Failure::Malformed.into();
+    };
+
+    let threads = match args.threads {
+        0 => std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+        threads => threads,
+    };
+    DECODE_THREADS.store(threads, Ordering::Relaxed);
+
+    let decode = |pixels: &mut [u8], out: &mut Vec<u8>| {
+        decode_pixels(
+            format,
+            level.width,
+            level.height,
+            pixels,
+            &args.decode_options,
+            out,
+        )
+        .is_some()
+    };
+
+    let allocations_before = ALLOCATIONS.load(Ordering::Relaxed);
+    if !decode(pixels, &mut Vec::new()) {
+        return Failure::Unsupported.into();
+    }
+    let fresh_allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations_before;
+
+    let mut times = Vec::with_capacity(args.iterations);
+    let mut decoded = Vec::new();
+    let allocations_before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..args.iterations {
+        let start = Instant::now();
+        decode(pixels, &mut decoded);
+        times.push(start.elapsed());
+    }
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations_before;
+
+    if times.is_empty() {
+        return ExitCode::SUCCESS;
+    }
+
+    times.sort();
+    let average = times.iter().sum::<Duration>() / times.len() as u32;
+    let median = times[times.len() / 2];
+    println!(
+        "{format:?} {}x{}, {} iterations on {threads} thread(s)",
+        level.width, level.height, args.iterations
+    );
+    println!("average: {average:?}");
+    println!("median:  {median:?}");
+    println!(
+        "speed:   {:.1} MB/s",
+        level.size as f64 / median.as_secs_f64() / 1e6
+    );
+    if cfg!(feature = "count-allocations") {
+        println!(
+            "allocations: {allocations} in total, {} without reusing the decoded buffer",
+            fresh_allocations * args.iterations
+        );
+    }
+
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,106 @@
+//! Cube maps storing their six faces in a single texture.
+
+use crate::transform::Rect;
+
+/// A face of a cube map, named after the axis it faces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Face {
+    #[value(name = "+x", alias = "posx")]
+    PositiveX,
+    #[value(name = "-x", alias = "negx")]
+    NegativeX,
+    #[value(name = "+y", alias = "posy")]
+    PositiveY,
+    #[value(name = "-y", alias = "negy")]
+    NegativeY,
+    #[value(name = "+z", alias = "posz")]
+    PositiveZ,
+    #[value(name = "-z", alias = "negz")]
+    NegativeZ,
+}
+
+impl Face {
+    /// Every face in the order a strip stores them.
+    pub const ALL: [Face; 6] = [
+        Face::PositiveX,
+        Face::NegativeX,
+        Face::PositiveY,
+        Face::NegativeY,
+        Face::PositiveZ,
+        Face::NegativeZ,
+    ];
+
+    /// Suffix of the file the face is written to, like `foo_posx.png`.
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Face::PositiveX => "posx",
+            Face::NegativeX => "negx",
+            Face::PositiveY => "posy",
+            Face::NegativeY => "negy",
+            Face::PositiveZ => "posz",
+            Face::NegativeZ => "negz",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Face::PositiveX => "+x",
+            Face::NegativeX => "-x",
+            Face::PositiveY => "+y",
+            Face::NegativeY => "-y",
+            Face::PositiveZ => "+z",
+            Face::NegativeZ => "-z",
+        }
+    }
+}
+
+/// How the faces of a cube map are arranged in the texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Layout {
+    /// Square faces stacked from top to bottom in the order +x, -x, +y, -y, +z, -z,
+    /// so the texture is six times as high as it is wide
+    Strip,
+    /// A horizontal cross four faces wide and three high, with -x, +z, +x and -z
+    /// in the middle row and +y and -y above and below +z
+    Cross,
+}
+
+impl Layout {
+    /// Column and row of every face of a cross, in the order of [`Face::ALL`].
+    const CROSS: [(u32, u32); 6] = [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)];
+
+    /// Size of a face of a `width` by `height` texture in this layout, `None` if
+    /// the texture doesn't have the proportions the layout needs.
+    pub fn face_size(self, width: u32, height: u32) -> Option<u32> {
+        let (columns, rows) = match self {
+            Layout::Strip => (1, 6),
+            Layout::Cross => (4, 3),
+        };
+        let size = width / columns;
+        (size > 0 && width == size * columns && height == size * rows).then_some(size)
+    }
+
+    /// Region of `face` in a texture whose faces are `size` pixels wide and high.
+    pub fn face_rect(self, face: Face, size: u32) -> Rect {
+        let index = Face::ALL.iter().position(|&other| other == face).unwrap();
+        let (column, row) = match self {
+            Layout::Strip => (0, index as u32),
+            Layout::Cross => Self::CROSS[index],
+        };
+        Rect {
+            x: column * size,
+            y: row * size,
+            width: size,
+            height: size,
+        }
+    }
+
+    /// Describes where the faces of `size` pixels are, for `info`.
+    pub fn describe(self, size: u32) -> String {
+        let faces = Face::ALL.map(|face| {
+            let rect = self.face_rect(face, size);
+            format!("{} at {},{}", face.name(), rect.x, rect.y)
+        });
+        faces.join(", ")
+    }
+}
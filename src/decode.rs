@@ -0,0 +1,266 @@
+use std::{
+    io::{self, Read, Seek, SeekFrom, Write},
+    ops::Range,
+};
+
+use crate::{
+    decode_in_place, decode_into, mip_levels, narrow_to_8_bit,
+    transform::{self, Rect, Swizzle},
+    AlphaExpansion, DecodeOptions, TexError, TexFormat, TexHeader, BAND_HEIGHT, MAGIC,
+};
+
+/// A decoded texture with straight 8-bit RGBA pixels stored row by row.
+///
+/// The library returns it, and `png2tex` and `diff` work on it. The transforms
+/// and writers of `tex2png` take plain buffers with a pixel size instead, since
+/// they also handle the grayscale, RGB and 16-bit images this can't hold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TexImage {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+impl TexImage {
+    /// The RGBA bytes of row `y`, panics if it is out of bounds.
+    pub fn row(&self, y: u32) -> &[u8] {
+        let stride = self.width as usize * 4;
+        &self.rgba[y as usize * stride..][..stride]
+    }
+
+    /// The RGBA value of the pixel at `x`, `y`, panics if it is out of bounds.
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        assert!(
+            x < self.width,
+            "pixel {x} is outside of a row of {}",
+            self.width
+        );
+        self.row(y)[x as usize * 4..][..4].try_into().unwrap()
+    }
+
+    /// All pixels as RGBA bytes, row by row.
+    pub fn as_rgba_bytes(&self) -> &[u8] {
+        &self.rgba
+    }
+
+    /// Copies the `rect` region out of the image, which has to lie within it.
+    pub fn crop(&self, rect: Rect) -> TexImage {
+        assert!(rect.fits_within(self.width, self.height));
+        TexImage {
+            width: rect.width,
+            height: rect.height,
+            rgba: transform::crop(&self.rgba, self.width, 4, rect),
+        }
+    }
+
+    /// Rearranges the channels of every pixel.
+    pub fn swizzle(&self, swizzle: Swizzle) -> TexImage {
+        TexImage {
+            width: self.width,
+            height: self.height,
+            rgba: swizzle.apply(&self.rgba),
+        }
+    }
+}
+
+/// Error returned when decoding a texture from a reader.
+#[derive(Debug)]
+pub enum DecodeError {
+    Io(std::io::Error),
+    Tex(TexError),
+    /// Writing the decoded image failed, only returned by [`decode_stream`].
+    Encode(png::EncodingError),
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DecodeError::Io(error) => write!(f, "failed to read texture: {error}"),
+            DecodeError::Tex(error) => error.fmt(f),
+            DecodeError::Encode(error) => write!(f, "failed to write PNG: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DecodeError::Io(error) => Some(error),
+            DecodeError::Tex(error) => Some(error),
+            DecodeError::Encode(error) => Some(error),
+        }
+    }
+}
+
+impl From<std::io::Error> for DecodeError {
+    fn from(error: std::io::Error) -> Self {
+        DecodeError::Io(error)
+    }
+}
+
+impl From<TexError> for DecodeError {
+    fn from(error: TexError) -> Self {
+        DecodeError::Tex(error)
+    }
+}
+
+impl From<png::EncodingError> for DecodeError {
+    fn from(error: png::EncodingError) -> Self {
+        DecodeError::Encode(error)
+    }
+}
+
+/// Options the library decodes with, matching `tex2png --expand-rgba`.
+const OPTIONS: DecodeOptions = DecodeOptions {
+    a8_as: AlphaExpansion::White,
+    expand_rgba: true,
+    pixel_endian: None,
+};
+
+/// Validates the header of a texture and returns its format, dimensions and the
+/// range of `bytes` holding the pixels of the base level.
+fn base_level(bytes: &[u8]) -> Result<(TexFormat, usize, usize, Range<usize>), TexError> {
+    let header = TexHeader::parse(bytes).ok_or(TexError::TooShort)?;
+    let (format, width, height, size) = check_header(&header)?;
+    let range = header.pixels_range(bytes.len())?;
+    if range.len() < size {
+        return Err(TexError::Truncated("pixel data"));
+    }
+    Ok((format, width, height, range.start..range.start + size))
+}
+
+/// Checks that `header` describes a texture this build can decode and returns its
+/// format, dimensions and the size of the pixels of the base level.
+fn check_header(header: &TexHeader) -> Result<(TexFormat, usize, usize, usize), TexError> {
+    if header.magic != MAGIC {
+        return Err(TexError::Magic(header.magic));
+    }
+    if header.version != 2 {
+        return Err(TexError::Version(header.version));
+    }
+    let format =
+        TexFormat::from_value(header.format).ok_or(TexError::UnknownFormat(header.format))?;
+    if !format.is_decodable() {
+        return Err(TexError::Unsupported(format));
+    }
+
+    let (width, height) = (header.width.max(0) as usize, header.height.max(0) as usize);
+    let level = mip_levels(format, width, height, 1)[0];
+    Ok((format, width, height, level.size))
+}
+
+/// Decodes the base level of a texture to RGBA like [`decode_tex`], but into `out`
+/// after clearing it, returning the dimensions of the texture.
+///
+/// Reusing `out` for many textures of the same size avoids allocating for every one.
+pub fn decode_tex_into(bytes: &[u8], out: &mut Vec<u8>) -> Result<(u32, u32), TexError> {
+    let (format, width, height, range) = base_level(bytes)?;
+    decode_level(format, width, height, &bytes[range], out)?;
+    Ok((width as u32, height as u32))
+}
+
+/// Decodes the pixels of a `width` by `height` level to 8-bit RGBA into `out`.
+fn decode_level(
+    format: TexFormat,
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+    out: &mut Vec<u8>,
+) -> Result<(), TexError> {
+    decode_into(format, width, height, pixels, &OPTIONS, out)
+        .ok_or(TexError::Unsupported(format))?;
+    if format.is_16_bit() {
+        narrow_to_8_bit(out);
+    }
+    Ok(())
+}
+
+/// Decodes the base level of a texture to RGBA.
+///
+/// Every format is expanded to 8-bit RGBA: alpha-only textures become white with
+/// the stored alpha, luminance textures gray and formats without alpha opaque.
+/// 16-bit channels are narrowed to 8 bits and pixels packed into 16 bits are read
+/// as little-endian. This matches `tex2png --expand-rgba` besides the narrowing.
+pub fn decode_tex(bytes: &[u8]) -> Result<TexImage, TexError> {
+    let mut rgba = Vec::new();
+    let (width, height) = decode_tex_into(bytes, &mut rgba)?;
+    Ok(TexImage {
+        width,
+        height,
+        rgba,
+    })
+}
+
+/// Reads a whole texture from `reader` and decodes it like [`decode_tex`].
+pub fn decode_tex_from_reader<R: Read>(mut reader: R) -> Result<TexImage, DecodeError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    // The pixels are decoded where they were read to when possible, so decoding
+    // doesn't need a second buffer next to the whole file.
+    let (format, width, height, range) = base_level(&bytes)?;
+    bytes.truncate(range.end);
+    bytes.drain(..range.start);
+    let rgba = if decode_in_place(format, &mut bytes, &OPTIONS) {
+        bytes
+    } else {
+        let mut rgba = Vec::new();
+        decode_level(format, width, height, &bytes, &mut rgba)?;
+        rgba
+    };
+    Ok(TexImage {
+        width: width as u32,
+        height: height as u32,
+        rgba,
+    })
+}
+
+/// Decodes the base level of a texture read from `reader` like [`decode_tex`] and
+/// writes it to `writer` as an 8-bit RGBA PNG, returning the dimensions of the texture.
+///
+/// Only a band of rows of the texture and of the image is held in memory at a time,
+/// so this can convert textures larger than the available memory. PVRTC textures
+/// can't be decoded a band at a time and are rejected as unsupported.
+pub fn decode_stream<R: Read + Seek, W: Write>(
+    mut reader: R,
+    writer: W,
+) -> Result<(u32, u32), DecodeError> {
+    let mut header = [0; TexHeader::SIZE];
+    reader
+        .read_exact(&mut header)
+        .map_err(|error| match error.kind() {
+            io::ErrorKind::UnexpectedEof => DecodeError::Tex(TexError::TooShort),
+            _ => DecodeError::Io(error),
+        })?;
+    let header = TexHeader::parse(&header).unwrap();
+    let (format, width, height, size) = check_header(&header)?;
+    if format.is_pvrtc() {
+        return Err(TexError::Unsupported(format).into());
+    }
+
+    let tex_len = reader.seek(SeekFrom::End(0))?;
+    let range = header.pixels_range(usize::try_from(tex_len).unwrap_or(usize::MAX))?;
+    if range.len() < size {
+        return Err(TexError::Truncated("pixel data").into());
+    }
+    reader.seek(SeekFrom::Start(range.start as u64))?;
+
+    let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut png = encoder.write_header()?;
+    let mut writer = png.stream_writer()?;
+    let mut pixels = Vec::new();
+    let mut rgba = Vec::new();
+    for start in (0..height).step_by(BAND_HEIGHT) {
+        let rows = BAND_HEIGHT.min(height - start);
+        pixels.resize(format.expected_pixel_size(width, rows), 0);
+        reader.read_exact(&mut pixels)?;
+        decode_level(format, width, rows, &pixels, &mut rgba)?;
+        writer.write_all(&rgba).map_err(png::EncodingError::from)?;
+    }
+    writer.finish()?;
+    png.finish()?;
+
+    Ok((width as u32, height as u32))
+}
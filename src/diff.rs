@@ -0,0 +1,175 @@
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use crate::{
+    decode_pixels, mip_levels, narrow_to_8_bit, parse_texture, png2tex, take_pixels, zip,
+    DecodeOptions, Failure, TexImage, UnknownFormat,
+};
+
+#[derive(clap::Parser)]
+pub struct Diff {
+    /// Texture or PNG to compare
+    a: PathBuf,
+    /// Texture or PNG to compare against
+    b: PathBuf,
+    /// Quality metrics to report in addition to the number of differing pixels
+    #[clap(long, value_enum, value_delimiter = ',', default_values_t = [Metric::Psnr, Metric::Ssim])]
+    metric: Vec<Metric>,
+    #[clap(flatten)]
+    decode_options: DecodeOptions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Metric {
+    /// Peak signal-to-noise ratio over all channels, in decibels
+    Psnr,
+    /// Mean structural similarity of all channels, between 0 and 1
+    Ssim,
+}
+
+/// Loads the base level of a texture, or a PNG file, as RGBA.
+fn load(path: &Path, options: &DecodeOptions) -> Result<TexImage, (Failure, String)> {
+    if path.extension().is_some_and(|ext| ext == "png") {
+        return png2tex::read_png(path);
+    }
+
+    let malformed = |error: String| (Failure::Malformed, error);
+    let mut tex = zip::read(path).map_err(|error| (Failure::Io, error))?;
+    let (header, format) = parse_texture(&tex, UnknownFormat::Reject, false)
+        .map_err(|failure| (failure, "invalid texture".to_string()))?;
+    let levels = mip_levels(
+        format,
+        header.width as usize,
+        header.height as usize,
+        (header.mipmaps as usize).max(1),
+    );
+    let level = levels[0];
+    let range = header
+        .pixels_range(tex.len())
+        .map_err(|e| malformed(e.to_string()))?;
+    let pixels = take_pixels(&mut tex[range], &levels, 0)
+        .ok_or_else(|| malformed("pixel data is too short".to_string()))?;
+    let mut decoded = Vec::new();
+    let Some((buffer, color_type)) = decode_pixels(
+        format,
+        level.width,
+        level.height,
+        pixels,
+        options,
+        &mut decoded,
+    ) else {
+        return Err((Failure::Unsupported, "unsupported format".to_string()));
+    };
+
+    let mut buffer = buffer.to_vec();
+    if format.is_16_bit() {
+        narrow_to_8_bit(&mut buffer);
+    }
+
+    Ok(TexImage {
+        width: level.width as u32,
+        height: level.height as u32,
+        rgba: png2tex::expand_to_rgba(buffer, color_type),
+    })
+}
+
+fn psnr(a: &[u8], b: &[u8]) -> f64 {
+    let squared_error = a
+        .iter()
+        .zip(b)
+        .map(|(&a, &b)| (a as f64 - b as f64).powi(2))
+        .sum::<f64>();
+    let mse = squared_error / a.len() as f64;
+    10.0 * (255.0 * 255.0 / mse).log10()
+}
+
+/// Side length of the windows SSIM is computed over.
+const SSIM_WINDOW: usize = 8;
+
+/// Mean SSIM over 8x8 windows spaced 4 pixels apart, averaged over all channels.
+///
+/// Images smaller than a window are compared as a single window.
+fn ssim(a: &[u8], b: &[u8], width: usize, height: usize) -> f64 {
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+    let (window_width, window_height) = (SSIM_WINDOW.min(width), SSIM_WINDOW.min(height));
+    let step = SSIM_WINDOW / 2;
+    let mut total = 0.0;
+    let mut count = 0;
+    for y in (0..=height - window_height).step_by(step) {
+        for x in (0..=width - window_width).step_by(step) {
+            for channel in 0..4 {
+                let samples = (y..y + window_height).flat_map(|y| {
+                    (x..x + window_width).map(move |x| (y * width + x) * 4 + channel)
+                });
+                let n = (window_width * window_height) as f64;
+                let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) =
+                    (0.0, 0.0, 0.0, 0.0, 0.0);
+                for i in samples {
+                    let (a, b) = (a[i] as f64, b[i] as f64);
+                    sum_a += a;
+                    sum_b += b;
+                    sum_aa += a * a;
+                    sum_bb += b * b;
+                    sum_ab += a * b;
+                }
+
+                let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+                let variance_a = sum_aa / n - mean_a * mean_a;
+                let variance_b = sum_bb / n - mean_b * mean_b;
+                let covariance = sum_ab / n - mean_a * mean_b;
+                total += (2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2)
+                    / ((mean_a * mean_a + mean_b * mean_b + C1) * (variance_a + variance_b + C2));
+                count += 1;
+            }
+        }
+    }
+    total / count as f64
+}
+
+/// Compares two decoded images, printing how many pixels differ and the requested metrics.
+pub fn diff(args: &Diff) -> ExitCode {
+    let read = |path: &PathBuf| {
+        load(path, &args.decode_options).map_err(|(failure, error)| {
+            eprintln!("Failed to read {}: {error}", path.display());
+            failure
+        })
+    };
+    let (a, b) = match (read(&args.a), read(&args.b)) {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(failure), _) | (_, Err(failure)) => return failure.into(),
+    };
+    if (a.width, a.height) != (b.width, b.height) {
+        eprintln!(
+            "Images have different sizes: {}x{} and {}x{}",
+            a.width, a.height, b.width, b.height
+        );
+        return Failure::Malformed.into();
+    }
+    let (width, height) = (a.width as usize, a.height as usize);
+    let (a, b) = (a.as_rgba_bytes(), b.as_rgba_bytes());
+
+    let pixels = width * height;
+    let differing = a
+        .chunks_exact(4)
+        .zip(b.chunks_exact(4))
+        .filter(|(a, b)| a != b)
+        .count();
+    let max_difference = a.iter().zip(b).map(|(&a, &b)| a.abs_diff(b)).max();
+    println!(
+        "differing pixels: {differing} of {pixels} ({:.2}%)",
+        differing as f64 * 100.0 / pixels as f64
+    );
+    println!("max difference:   {}", max_difference.unwrap_or(0));
+    for metric in &args.metric {
+        match metric {
+            Metric::Psnr => println!("psnr:             {:.2} dB", psnr(a, b)),
+            Metric::Ssim => println!("ssim:             {:.4}", ssim(a, b, width, height)),
+        }
+    }
+
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,222 @@
+//! Decoder for ETC2 RGB and ETC2 RGBA (ETC2 color with EAC alpha) textures.
+//!
+//! Both formats store 4x4 pixel blocks in row-major order, the color block is
+//! 64 bits large and RGBA blocks prefix it with a 64-bit EAC alpha block.
+
+/// Intensity modifiers of the individual and differential modes, indexed by
+/// the table codeword of a subblock.
+const MODIFIERS: [[i32; 2]; 8] = [
+    [2, 8],
+    [5, 17],
+    [9, 29],
+    [13, 42],
+    [18, 60],
+    [24, 80],
+    [33, 106],
+    [47, 183],
+];
+
+/// Distances between the paint colors of the T and H modes.
+const DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+/// Alpha modifiers of EAC blocks, indexed by the table index of the block.
+const ALPHA_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+fn clamp(value: i32) -> u8 {
+    value.clamp(0, 255) as u8
+}
+
+fn extend_4(value: u8) -> u8 {
+    (value << 4) | value
+}
+
+fn extend_5(value: u8) -> u8 {
+    (value << 3) | (value >> 2)
+}
+
+fn extend_6(value: u8) -> u8 {
+    (value << 2) | (value >> 4)
+}
+
+fn extend_7(value: u8) -> u8 {
+    (value << 1) | (value >> 6)
+}
+
+/// Adds `offset` to every channel of `color`, clamping the results.
+fn offset_color(color: [u8; 3], offset: i32) -> [u8; 3] {
+    color.map(|channel| clamp(channel as i32 + offset))
+}
+
+/// Two bit index of pixel `i` of a block, pixels are numbered column by column.
+fn pixel_index(block: &[u8; 8], i: usize) -> usize {
+    let indices = u32::from_be_bytes(block[4..].try_into().unwrap());
+    (((indices >> (i + 16)) & 1) << 1 | ((indices >> i) & 1)) as usize
+}
+
+/// Decodes an ETC2 color block to RGB pixels, column by column.
+fn decode_color_block(block: &[u8; 8]) -> [[u8; 3]; 16] {
+    let [b0, b1, b2, b3, ..] = *block;
+    let mut pixels = [[0; 3]; 16];
+
+    if b3 & 2 == 0 {
+        // Individual mode, two 4-bit colors.
+        let first = [b0 >> 4, b1 >> 4, b2 >> 4].map(extend_4);
+        let second = [b0 & 0xF, b1 & 0xF, b2 & 0xF].map(extend_4);
+        decode_subblocks(block, first, second, &mut pixels);
+        return pixels;
+    }
+
+    // Differential mode with a 5-bit color and a signed 3-bit delta for the
+    // second one, deltas that leave the 5-bit range select the other modes.
+    let base = [b0 >> 3, b1 >> 3, b2 >> 3];
+    let delta = [b0, b1, b2].map(|byte| ((byte << 5) as i8 >> 5) as i32);
+    let second = [0, 1, 2].map(|i| base[i] as i32 + delta[i]);
+    let in_range = |value: i32| (0..32).contains(&value);
+
+    if !in_range(second[0]) {
+        // T mode
+        let first = [((b0 >> 1) & 0xC) | (b0 & 3), b1 >> 4, b1 & 0xF].map(extend_4);
+        let second = [b2 >> 4, b2 & 0xF, b3 >> 4].map(extend_4);
+        let distance = DISTANCES[(((b3 >> 1) & 6) | (b3 & 1)) as usize];
+        let paint = [
+            first,
+            offset_color(second, distance),
+            second,
+            offset_color(second, -distance),
+        ];
+        for (i, pixel) in pixels.iter_mut().enumerate() {
+            *pixel = paint[pixel_index(block, i)];
+        }
+    } else if !in_range(second[1]) {
+        // H mode
+        let first = [
+            (b0 >> 3) & 0xF,
+            ((b0 & 7) << 1) | ((b1 >> 4) & 1),
+            (b1 & 8) | ((b1 & 3) << 1) | (b2 >> 7),
+        ];
+        let second = [
+            (b2 >> 3) & 0xF,
+            ((b2 & 7) << 1) | (b3 >> 7),
+            (b3 >> 3) & 0xF,
+        ];
+        let packed = |[r, g, b]: [u8; 3]| (r as u32) << 8 | (g as u32) << 4 | b as u32;
+        let ordering = (packed(first) >= packed(second)) as u8;
+        let distance = DISTANCES[((b3 & 4) | ((b3 & 1) << 1) | ordering) as usize];
+
+        let (first, second) = (first.map(extend_4), second.map(extend_4));
+        let paint = [
+            offset_color(first, distance),
+            offset_color(first, -distance),
+            offset_color(second, distance),
+            offset_color(second, -distance),
+        ];
+        for (i, pixel) in pixels.iter_mut().enumerate() {
+            *pixel = paint[pixel_index(block, i)];
+        }
+    } else if !in_range(second[2]) {
+        // Planar mode, colors are interpolated from the origin and the
+        // horizontal and vertical corners.
+        let [_, _, _, _, b4, b5, b6, b7] = *block;
+        let origin = [
+            extend_6((b0 >> 1) & 0x3F),
+            extend_7(((b0 & 1) << 6) | ((b1 >> 1) & 0x3F)),
+            extend_6(((b1 & 1) << 5) | (b2 & 0x18) | ((b2 & 3) << 1) | (b3 >> 7)),
+        ];
+        let horizontal = [
+            extend_6((((b3 >> 2) & 0x1F) << 1) | (b3 & 1)),
+            extend_7(b4 >> 1),
+            extend_6(((b4 & 1) << 5) | (b5 >> 3)),
+        ];
+        let vertical = [
+            extend_6(((b5 & 7) << 3) | (b6 >> 5)),
+            extend_7(((b6 & 0x1F) << 2) | (b7 >> 6)),
+            extend_6(b7 & 0x3F),
+        ];
+        for (i, pixel) in pixels.iter_mut().enumerate() {
+            let (x, y) = ((i / 4) as i32, (i % 4) as i32);
+            *pixel = [0, 1, 2].map(|c| {
+                let (o, h, v) = (origin[c] as i32, horizontal[c] as i32, vertical[c] as i32);
+                clamp((x * (h - o) + y * (v - o) + 4 * o + 2) >> 2)
+            });
+        }
+    } else {
+        let first = base.map(extend_5);
+        let second = second.map(|value| extend_5(value as u8));
+        decode_subblocks(block, first, second, &mut pixels);
+    }
+
+    pixels
+}
+
+/// Decodes the two subblocks of the individual and differential modes.
+fn decode_subblocks(block: &[u8; 8], first: [u8; 3], second: [u8; 3], pixels: &mut [[u8; 3]; 16]) {
+    let flipped = block[3] & 1 != 0;
+    let tables = [
+        MODIFIERS[(block[3] >> 5) as usize],
+        MODIFIERS[((block[3] >> 2) & 7) as usize],
+    ];
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        let (x, y) = (i / 4, i % 4);
+        let subblock = if flipped { y >= 2 } else { x >= 2 } as usize;
+        let [small, large] = tables[subblock];
+        let modifier = [small, large, -small, -large][pixel_index(block, i)];
+        *pixel = offset_color([first, second][subblock], modifier);
+    }
+}
+
+/// Decodes an EAC alpha block, column by column.
+fn decode_alpha_block(block: &[u8; 8]) -> [u8; 16] {
+    let base = block[0] as i32;
+    let multiplier = (block[1] >> 4) as i32;
+    let modifiers = ALPHA_MODIFIERS[(block[1] & 0xF) as usize];
+    let indices = u64::from_be_bytes(*block);
+    std::array::from_fn(|i| {
+        let index = (indices >> (45 - 3 * i)) & 7;
+        clamp(base + modifiers[index as usize] * multiplier)
+    })
+}
+
+/// Decodes ETC2 pixel data to RGBA into `image`, which holds four bytes for each
+/// of the `width * height` pixels.
+///
+/// With `alpha` every block is preceded by an EAC alpha block, otherwise the
+/// texture is opaque. Blocks crossing the right or bottom edge are cut off at
+/// the texture's size.
+pub fn decode(pixels: &[u8], width: usize, height: usize, alpha: bool, image: &mut [u8]) {
+    let block_size = if alpha { 16 } else { 8 };
+    let blocks_wide = width.div_ceil(4);
+    for (i, block) in pixels.chunks_exact(block_size).enumerate() {
+        let (block_x, block_y) = (i % blocks_wide * 4, i / blocks_wide * 4);
+        let (alpha_block, color_block) = block.split_at(block_size - 8);
+        let colors = decode_color_block(color_block.try_into().unwrap());
+        let alphas = match alpha_block.try_into() {
+            Ok(alpha_block) => decode_alpha_block(alpha_block),
+            Err(_) => [0xFF; 16],
+        };
+
+        for (j, (color, alpha)) in colors.iter().zip(alphas).enumerate() {
+            let (x, y) = (block_x + j / 4, block_y + j % 4);
+            if x < width && y < height {
+                let offset = (y * width + x) * 4;
+                image[offset..offset + 4].copy_from_slice(&[color[0], color[1], color[2], alpha]);
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::{path::PathBuf, process::ExitCode};
+
+use crate::{
+    full_mip_count, mip_levels,
+    png2tex::{self, TexLayout},
+    Failure, PixelEndian, TexFormat,
+};
+
+#[derive(clap::Parser)]
+pub struct GenFixture {
+    /// Path of the texture to write
+    output_path: PathBuf,
+    #[clap(long, value_enum, default_value_t = TexFormat::Bgra8888)]
+    format: TexFormat,
+    #[clap(long, default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..=i16::MAX as i64))]
+    width: u16,
+    #[clap(long, default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..=i16::MAX as i64))]
+    height: u16,
+    #[clap(long, value_enum, default_value_t = Pattern::Gradient)]
+    pattern: Pattern,
+    /// Number of mipmap levels to store, each with the pattern drawn at its size
+    #[clap(long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..))]
+    mips: u8,
+    /// Also store the bitmap marking fully opaque texels
+    #[clap(long)]
+    bitmap: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Pattern {
+    /// Red increasing to the right, green downwards, blue along the diagonal and
+    /// alpha decreasing to the right
+    Gradient,
+    /// Opaque white and transparent black squares of 2x2 pixels
+    Checkerboard,
+    /// Pseudo-random pixels, the same for every run; the only pattern of formats
+    /// that can't be encoded, which get random pixel data instead
+    Noise,
+}
+
+/// Scales `value` out of `max` to the range of a byte.
+fn ramp(value: usize, max: usize) -> u8 {
+    (value * 0xFF / max.max(1)) as u8
+}
+
+/// Xorshift generator, which keeps noise fixtures identical everywhere.
+struct Noise(u32);
+
+impl Noise {
+    fn next_byte(&mut self) -> u8 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 24) as u8
+    }
+}
+
+impl Pattern {
+    /// RGBA pixels of the pattern at a size of `width` by `height`.
+    fn draw(self, width: usize, height: usize, noise: &mut Noise) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(width * height * 4);
+        for y in 0..height {
+            for x in 0..width {
+                rgba.extend_from_slice(&match self {
+                    Pattern::Gradient => [
+                        ramp(x, width - 1),
+                        ramp(y, height - 1),
+                        ramp(x + y, width + height - 2),
+                        0xFF - ramp(x, width - 1),
+                    ],
+                    Pattern::Checkerboard if (x / 2 + y / 2) % 2 == 0 => [0xFF; 4],
+                    Pattern::Checkerboard => [0x00; 4],
+                    Pattern::Noise => std::array::from_fn(|_| noise.next_byte()),
+                });
+            }
+        }
+        rgba
+    }
+}
+
+/// Writes a small texture with a known pattern, for tests and reproducible bug reports.
+pub fn gen_fixture(args: &GenFixture) -> ExitCode {
+    let format = args.format;
+    if !format.is_encodable() && args.pattern != Pattern::Noise {
+        eprintln!(
+            "{} can't be encoded, only --pattern noise is supported for it",
+            format.name()
+        );
+        return Failure::Usage.into();
+    }
+
+    let (width, height) = (args.width as usize, args.height as usize);
+    let level_count = (args.mips as usize).min(full_mip_count(width, height));
+    let mut noise = Noise(0x2545_F491);
+    let mut pixels = Vec::new();
+    let mut bitmap = None;
+    for level in mip_levels(format, width, height, level_count) {
+        if !format.is_encodable() {
+            pixels.extend((0..level.size).map(|_| noise.next_byte()));
+            continue;
+        }
+
+        let rgba = args.pattern.draw(level.width, level.height, &mut noise);
+        if bitmap.is_none() && args.bitmap {
+            bitmap = Some(png2tex::opaque_bitmap(&rgba));
+        }
+        png2tex::encode_pixels(format, &rgba, PixelEndian::Little, &mut pixels);
+    }
+    if args.bitmap && bitmap.is_none() {
+        // Noise is random data in formats that can't be encoded, so mark every texel.
+        bitmap = Some(vec![0xFF; (width * height).div_ceil(8)]);
+    }
+
+    let tex = png2tex::assemble_tex(
+        TexLayout {
+            version: 2,
+            format: format.into(),
+            mipmaps: level_count as u8,
+            width: width as i16,
+            height: height as i16,
+            scale: 1,
+        },
+        &pixels,
+        bitmap.as_deref(),
+    );
+    let Some(tex) = tex else {
+        eprintln!("A {width}x{height} texture is too large for a tex file");
+        return Failure::Unsupported.into();
+    };
+    if let Err(error) = std::fs::write(&args.output_path, tex) {
+        eprintln!("Failed to write {}: {error}", args.output_path.display());
+        return Failure::Io.into();
+    }
+
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,141 @@
+//! Writer for GIF images with a global palette, animated when given several frames.
+
+use std::collections::HashMap;
+
+/// Largest number of entries the LZW code table of a GIF image can hold.
+const MAX_CODES: u16 = 4096;
+
+/// A frame of palette indices, drawn in the top left corner of the image.
+pub struct GifFrame {
+    pub width: u16,
+    pub height: u16,
+    pub indices: Vec<u8>,
+}
+
+/// Packs variable width codes into bytes, least significant bit first.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, width: u32) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += width;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Compresses palette indices with the LZW variant of GIF, starting with codes
+/// one bit wider than `min_code_size`.
+fn compress(indices: &[u8], min_code_size: u32) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    let mut codes = HashMap::<(u16, u8), u16>::new();
+    let mut next_code = end + 1;
+    let mut width = min_code_size + 1;
+
+    let mut writer = BitWriter::default();
+    writer.write(clear, width);
+    let Some((&first, rest)) = indices.split_first() else {
+        writer.write(end, width);
+        return writer.finish();
+    };
+
+    let mut prefix = first as u16;
+    for &index in rest {
+        if let Some(&code) = codes.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+
+        writer.write(prefix, width);
+        codes.insert((prefix, index), next_code);
+        // Decoders widen codes once the table reaches the next power of two.
+        if next_code == 1 << width && width < 12 {
+            width += 1;
+        }
+        next_code += 1;
+        if next_code == MAX_CODES {
+            writer.write(clear, width);
+            codes.clear();
+            next_code = end + 1;
+            width = min_code_size + 1;
+        }
+        prefix = index as u16;
+    }
+    writer.write(prefix, width);
+    writer.write(end, width);
+    writer.finish()
+}
+
+/// Encodes `frames` into a GIF of `width` by `height` pixels with the colors of
+/// `palette`, given as RGB triples of at most 256 colors.
+///
+/// Several frames are shown `delay` hundredths of a second each and loop forever,
+/// the image is cleared to transparent before every frame if `transparent` is set.
+pub fn encode(
+    (width, height): (u16, u16),
+    frames: &[GifFrame],
+    palette: &[u8],
+    transparent: Option<u8>,
+    delay: u16,
+) -> Vec<u8> {
+    // The color table holds a power of two entries, at least two.
+    let colors = (palette.len() / 3).max(2);
+    let table_bits = colors.next_power_of_two().trailing_zeros();
+
+    let mut gif = Vec::new();
+    gif.extend_from_slice(b"GIF89a");
+    gif.extend_from_slice(&width.to_le_bytes());
+    gif.extend_from_slice(&height.to_le_bytes());
+    // A global color table with 8 bits per channel, no background color or aspect ratio.
+    gif.extend_from_slice(&[0xF0 | (table_bits as u8 - 1), 0, 0]);
+    gif.extend_from_slice(palette);
+    gif.resize(gif.len() + (3 << table_bits) - palette.len(), 0);
+
+    if frames.len() > 1 {
+        // NETSCAPE2.0 application extension, looping the animation forever.
+        gif.extend_from_slice(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00");
+    }
+
+    let min_code_size = table_bits.max(2);
+    for frame in frames {
+        if frames.len() > 1 || transparent.is_some() {
+            // Graphic control extension, disposing each frame to the background.
+            let disposal = if transparent.is_some() { 2 << 2 } else { 0 };
+            gif.extend_from_slice(&[0x21, 0xF9, 4, disposal | transparent.is_some() as u8]);
+            gif.extend_from_slice(&delay.to_le_bytes());
+            gif.extend_from_slice(&[transparent.unwrap_or(0), 0]);
+        }
+
+        gif.push(0x2C);
+        for value in [0, 0, frame.width, frame.height] {
+            gif.extend_from_slice(&value.to_le_bytes());
+        }
+        gif.push(0);
+
+        gif.push(min_code_size as u8);
+        for block in compress(&frame.indices, min_code_size).chunks(255) {
+            gif.push(block.len() as u8);
+            gif.extend_from_slice(block);
+        }
+        gif.push(0);
+    }
+
+    gif.push(0x3B);
+    gif
+}
@@ -0,0 +1,48 @@
+//! Writer for Windows icon files with PNG compressed images.
+
+/// Side lengths of the square images stored in icons, 256 is the largest size
+/// the format can describe.
+pub const SIZES: [u32; 4] = [16, 32, 48, 256];
+
+/// Sizes of the images an icon of a `side` by `side` image gets, all standard
+/// sizes not larger than the image or just the smallest one for tiny images.
+pub fn sizes_for(side: u32) -> Vec<u32> {
+    let sizes = SIZES
+        .into_iter()
+        .filter(|&size| size <= side)
+        .collect::<Vec<_>>();
+    if sizes.is_empty() {
+        vec![SIZES[0]]
+    } else {
+        sizes
+    }
+}
+
+/// Packs square PNG images, given with their side length, into an ICO file.
+pub fn pack(images: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    const DIRECTORY_SIZE: usize = 6;
+    const ENTRY_SIZE: usize = 16;
+
+    let mut ico = Vec::new();
+    ico.extend_from_slice(&0u16.to_le_bytes());
+    // Type 1 marks icons, 2 would be cursors.
+    ico.extend_from_slice(&1u16.to_le_bytes());
+    ico.extend_from_slice(&(images.len() as u16).to_le_bytes());
+
+    let mut offset = DIRECTORY_SIZE + ENTRY_SIZE * images.len();
+    for (size, png) in images {
+        // Sizes are stored in a byte where 0 stands for 256.
+        let side = (*size % 256) as u8;
+        ico.extend_from_slice(&[side, side, 0, 0]);
+        // One color plane of 32 bits per pixel.
+        ico.extend_from_slice(&1u16.to_le_bytes());
+        ico.extend_from_slice(&32u16.to_le_bytes());
+        ico.extend_from_slice(&(png.len() as u32).to_le_bytes());
+        ico.extend_from_slice(&(offset as u32).to_le_bytes());
+        offset += png.len();
+    }
+    for (_, png) in images {
+        ico.extend_from_slice(png);
+    }
+    ico
+}
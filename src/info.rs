@@ -0,0 +1,206 @@
+use std::{fmt::Write, path::PathBuf, process::ExitCode};
+
+use crate::{
+    check_magic, cubemap, layout_discrepancies, mip_levels, transform, Failure, MipLevel,
+    TexFormat, TexHeader,
+};
+
+#[derive(clap::Parser)]
+pub struct Info {
+    tex_path: PathBuf,
+    /// Print the header as a JSON object
+    #[clap(long)]
+    json: bool,
+    /// Only warn about files that don't start with the tex magic instead of rejecting them
+    #[clap(long)]
+    ignore_magic: bool,
+    /// Print the dimensions, file offset and size of every mipmap level instead of the header
+    #[clap(long)]
+    list_mipmaps: bool,
+}
+
+/// Quotes `value` as a JSON string, treating every byte as a Latin-1 character so
+/// arbitrary magic bytes can be represented.
+fn json_string(value: &[u8]) -> String {
+    let mut result = String::from("\"");
+    for &byte in value {
+        match byte {
+            b'"' => result.push_str("\\\""),
+            b'\\' => result.push_str("\\\\"),
+            b'\n' => result.push_str("\\n"),
+            0x20..=0x7E => result.push(byte as char),
+            _ => write!(result, "\\u{byte:04x}").unwrap(),
+        }
+    }
+    result.push('"');
+    result
+}
+
+fn print_json(header: &TexHeader, format: Option<TexFormat>, premultiplied: Option<bool>) {
+    let format_name = format.map_or("null".to_string(), |format| {
+        json_string(format.name().as_bytes())
+    });
+    let bits_per_pixel = format.map_or("null".to_string(), |format| {
+        format.bits_per_pixel().to_string()
+    });
+
+    println!("{{");
+    println!("  \"magic\": {},", json_string(&header.magic));
+    println!("  \"version\": {},", header.version);
+    println!("  \"format\": {},", header.format);
+    println!("  \"format_name\": {format_name},");
+    println!("  \"bits_per_pixel\": {bits_per_pixel},");
+    println!(
+        "  \"decodable\": {},",
+        format.is_some_and(TexFormat::is_decodable)
+    );
+    println!("  \"mipmaps\": {},", header.mipmaps);
+    println!("  \"opaque_bitmap\": {},", header.opaque_bitmap);
+    println!("  \"width\": {},", header.width);
+    println!("  \"height\": {},", header.height);
+    println!("  \"scale\": {},", header.scale);
+    println!("  \"pixels_offset\": {},", header.pixels_offset);
+    println!("  \"pixels_size\": {},", header.pixels_size);
+    println!("  \"bitmap_offset\": {},", header.bitmap_offset);
+    println!("  \"bitmap_size\": {},", header.bitmap_size);
+    println!(
+        "  \"likely_premultiplied\": {}",
+        premultiplied.map_or("null".to_string(), |premultiplied| premultiplied
+            .to_string())
+    );
+    println!("}}");
+}
+
+fn print_table(header: &TexHeader, format: Option<TexFormat>, premultiplied: Option<bool>) {
+    let format_name = match format {
+        Some(format) if format.is_decodable() => format.name().to_string(),
+        Some(format) => format!("{}, not decodable", format.name()),
+        None => "unknown".to_string(),
+    };
+
+    println!("version:       {}", header.version);
+    println!("format:        0x{:02X} ({format_name})", header.format);
+    if let Some(format) = format {
+        println!("bits/pixel:    {}", format.bits_per_pixel());
+    }
+    println!("mipmaps:       {}", header.mipmaps);
+    println!("opaque bitmap: {}", header.opaque_bitmap);
+    println!("size:          {}x{}", header.width, header.height);
+    println!("scale:         {}", header.scale);
+    println!(
+        "pixels:        {} bytes at offset {}",
+        header.pixels_size, header.pixels_offset
+    );
+    println!(
+        "bitmap:        {} bytes at offset {}",
+        header.bitmap_size, header.bitmap_offset
+    );
+    match premultiplied {
+        Some(true) => println!("alpha:         likely premultiplied"),
+        Some(false) => println!("alpha:         straight or opaque"),
+        None => {}
+    }
+    // Whether a texture is a cube map isn't recorded, only its proportions hint at it.
+    let (width, height) = (header.width.max(0) as u32, header.height.max(0) as u32);
+    for layout in [cubemap::Layout::Strip, cubemap::Layout::Cross] {
+        if let Some(size) = layout.face_size(width, height) {
+            println!(
+                "cube map:      {size}x{size} faces with --cube-layout {}: {}",
+                format!("{layout:?}").to_lowercase(),
+                layout.describe(size)
+            );
+        }
+    }
+}
+
+/// Prints the mipmap levels of a texture, with offsets from the start of the file.
+///
+/// Levels reaching past the end of the pixel data, or of the file if that ends
+/// first, are marked as truncated since they can't be extracted.
+fn print_levels(tex: &[u8], header: &TexHeader, levels: &[MipLevel], json: bool) {
+    let pixels_offset = header.pixels_offset.max(0) as usize;
+    let available =
+        (header.pixels_size.max(0) as usize).min(tex.len().saturating_sub(pixels_offset));
+    let truncated = |level: &MipLevel| level.offset + level.size > available;
+    if json {
+        println!("[");
+        for (i, level) in levels.iter().enumerate() {
+            println!(
+                "  {{\"level\": {i}, \"width\": {}, \"height\": {}, \"offset\": {}, \"size\": {}, \"truncated\": {}}}{}",
+                level.width,
+                level.height,
+                pixels_offset + level.offset,
+                level.size,
+                truncated(level),
+                if i + 1 < levels.len() { "," } else { "" }
+            );
+        }
+        println!("]");
+        return;
+    }
+
+    println!("level  width  height      offset        size");
+    for (i, level) in levels.iter().enumerate() {
+        println!(
+            "{i:>5}  {:>5}  {:>6}  {:>10}  {:>10}{}",
+            level.width,
+            level.height,
+            pixels_offset + level.offset,
+            level.size,
+            if truncated(level) { "  truncated" } else { "" }
+        );
+    }
+}
+
+pub fn info(args: &Info) -> ExitCode {
+    let tex = match std::fs::read(&args.tex_path) {
+        Ok(tex) => tex,
+        Err(error) => {
+            eprintln!("Failed to read {}: {error}", args.tex_path.display());
+            return Failure::Io.into();
+        }
+    };
+
+    let Some(header) = TexHeader::parse(&tex) else {
+        eprintln!("File is not a tex file: too short");
+        return Failure::Malformed.into();
+    };
+    if !check_magic(&header, args.ignore_magic) {
+        return Failure::Malformed.into();
+    }
+
+    for discrepancy in layout_discrepancies(&tex, &header) {
+        warning!("Unexpected texture layout: {discrepancy}");
+    }
+
+    let format = TexFormat::from_value(header.format);
+    if args.list_mipmaps {
+        let Some(format) = format else {
+            eprintln!(
+                "Can't lay out the mipmaps of unknown format 0x{:02X}",
+                header.format
+            );
+            return Failure::Unsupported.into();
+        };
+        let levels = mip_levels(
+            format,
+            header.width.max(0) as usize,
+            header.height.max(0) as usize,
+            (header.mipmaps as usize).max(1),
+        );
+        print_levels(&tex, &header, &levels, args.json);
+        return ExitCode::SUCCESS;
+    }
+
+    // Only textures that decode cleanly get their alpha checked.
+    let premultiplied = crate::decode_tex(&tex)
+        .ok()
+        .map(|image| transform::looks_premultiplied(image.as_rgba_bytes()));
+    if args.json {
+        print_json(&header, format, premultiplied);
+    } else {
+        print_table(&header, format, premultiplied);
+    }
+
+    ExitCode::SUCCESS
+}
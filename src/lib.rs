@@ -0,0 +1,3556 @@
+use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    cmp::Ordering,
+    collections::{BTreeMap, HashSet},
+    fmt::Debug,
+    io::IsTerminal,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::atomic::{self, AtomicBool, AtomicU8, AtomicUsize},
+    sync::mpsc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use clap::{Parser, ValueEnum};
+
+pub use decode::{
+    decode_stream, decode_tex, decode_tex_from_reader, decode_tex_into, DecodeError, TexImage,
+};
+
+/// Prints a warning, which fails the conversion under `tex2png --strict`.
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        $crate::print_warning(format_args!($($arg)*))
+    };
+}
+
+/// Prints a line to stderr, or captures it while converting on a worker thread.
+macro_rules! message {
+    ($($arg:tt)*) => {
+        $crate::print_line($crate::Stream::Stderr, format_args!($($arg)*))
+    };
+}
+
+/// Prints a line to stdout, or captures it while converting on a worker thread.
+macro_rules! output {
+    ($($arg:tt)*) => {
+        $crate::print_line($crate::Stream::Stdout, format_args!($($arg)*))
+    };
+}
+
+/// Prints details about what a conversion does when `-v` is given.
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::VERBOSITY.load(std::sync::atomic::Ordering::Relaxed) >= 1 {
+            $crate::print_line($crate::Stream::Stderr, format_args!("info: {}", format_args!($($arg)*)))
+        }
+    };
+}
+
+/// Prints internals like buffer sizes and timings when `-vv` is given.
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::VERBOSITY.load(std::sync::atomic::Ordering::Relaxed) >= 2 {
+            $crate::print_line($crate::Stream::Stderr, format_args!("debug: {}", format_args!($($arg)*)))
+        }
+    };
+}
+
+mod atlas;
+mod bench;
+mod cubemap;
+mod decode;
+mod diff;
+#[cfg(feature = "etc")]
+mod etc;
+mod fixture;
+#[cfg(feature = "image-formats")]
+mod gif;
+#[cfg(feature = "image-formats")]
+mod ico;
+mod info;
+#[cfg(feature = "image-formats")]
+mod netpbm;
+mod palette;
+mod png2tex;
+mod progress;
+#[cfg(feature = "pvrtc")]
+mod pvrtc;
+mod resize;
+pub mod swizzle;
+mod template;
+mod transform;
+mod watch;
+mod zip;
+
+#[derive(clap::Parser)]
+#[clap(after_help = "\
+Exit codes:
+  0  success
+  1  failure not covered below
+  2  invalid command line arguments or input paths
+  3  reading an input or writing an output failed
+  4  an input is not a valid texture or PNG file, or doesn't fit the options given
+  5  an input uses an unsupported format or version
+  6  an image could not be encoded
+
+With several inputs the code of the first failed conversion is used.")]
+struct Args {
+    #[clap(subcommand)]
+    command: Command,
+    /// Print the parsed header and chosen format of every texture, repeat as `-vv` to
+    /// also print buffer sizes, decoding paths and timings
+    #[clap(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    #[clap(name = "tex2png")]
+    Tex2Png(Box<Tex2Png>),
+    /// Print the header of a tex file
+    Info(info::Info),
+    #[clap(name = "png2tex")]
+    Png2Tex(png2tex::Png2Tex),
+    /// List all known texture formats and whether they can be converted
+    #[clap(alias = "list-formats")]
+    Formats,
+    /// Compare the decoded pixels of two textures or PNG files
+    Diff(diff::Diff),
+    /// Measure how fast a texture can be decoded
+    #[clap(hide = true)]
+    Bench(bench::Bench),
+    /// Write a small texture with a known pattern in any format
+    #[clap(hide = true)]
+    GenFixture(fixture::GenFixture),
+}
+
+#[derive(clap::Parser)]
+struct Tex2Png {
+    /// Textures to convert, directories and ZIP archives are converted recursively
+    ///
+    /// A single texture inside of an archive can be selected with `archive.zip!path/in/archive.tex`.
+    #[clap(required = true)]
+    tex_paths: Vec<PathBuf>,
+    /// Output file, or output directory when converting a directory or multiple files
+    /// or using --output-template.
+    ///
+    /// A single texture is converted into a directory, keeping its name, if the path
+    /// is an existing directory or ends in a path separator.
+    #[clap(short = 'o', long = "output")]
+    output_path: Option<PathBuf>,
+    /// Further PNG files to write the converted image to, separated by commas, which
+    /// decodes a single texture only once for several targets
+    #[clap(
+        long,
+        value_delimiter = ',',
+        conflicts_with_all = ["output_template", "mipmaps", "slice", "cell"]
+    )]
+    outputs: Vec<PathBuf>,
+    /// Format of the written files, chosen by the extension of each output by default
+    ///
+    /// Icons hold the image centered on a transparent square at the standard sizes of
+    /// 16, 32, 48 and 256 pixels, leaving out sizes larger than the image. GIFs use
+    /// --palette colors or 256, pixels with less than half alpha become transparent.
+    /// PPM images drop alpha, PAM images keep it.
+    #[clap(long, value_enum)]
+    output_format: Option<OutputFormat>,
+    /// Name output files after a template like `{stem}_{width}x{height}.png`, supported
+    /// placeholders are `{stem}`, `{ext}`, `{width}`, `{height}`, `{format}` and `{index}`
+    ///
+    /// `{index}` is the position of the texture in files with several concatenated ones,
+    /// which otherwise get it inserted before the extension.
+    #[clap(long, visible_alias = "name-template")]
+    output_template: Option<template::OutputTemplate>,
+    /// Create missing parent directories of output files, on by default when converting
+    /// into an output directory
+    #[clap(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    create_dirs: Option<bool>,
+    /// Show a progress bar while converting multiple files, or the decoded rows of a single
+    /// large file, on by default when stderr is a terminal
+    #[clap(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    progress: Option<bool>,
+    /// Number of files to convert at the same time, 0 uses one thread per CPU core
+    ///
+    /// Messages about each file are still printed together and in input order. A single
+    /// large texture is split into bands of rows decoded on this many threads instead.
+    #[clap(long, default_value_t = 1)]
+    threads: usize,
+    /// Read up to this many inputs ahead on a separate thread while converting, so
+    /// reading files overlaps decoding and writing them; 0 reads each file when its
+    /// conversion starts
+    #[clap(long, default_value_t = 0)]
+    queue_depth: usize,
+    /// Only check that the inputs can be decoded, without writing any files
+    #[clap(long, conflicts_with_all = ["emit_bitmap", "watch"])]
+    verify: bool,
+    /// Keep running and reconvert inputs whenever they change
+    #[clap(long)]
+    watch: bool,
+    /// Also convert textures whose output is newer than them, which are skipped when
+    /// converting a directory or multiple files
+    #[clap(long)]
+    force: bool,
+    /// Only convert textures modified after this time, given in seconds since the Unix
+    /// epoch or as a UTC date like `2024-05-01` or `2024-05-01T12:30:00`
+    #[clap(long, value_parser = parse_time)]
+    since: Option<SystemTime>,
+    /// Parse the headers of the inputs and print where each output would be written,
+    /// and which inputs would be skipped, without writing anything
+    #[clap(long, conflicts_with_all = ["verify", "watch"])]
+    dry_run: bool,
+    /// Read every PNG back after writing it and fail if it doesn't hold exactly the
+    /// image data that was encoded
+    #[clap(long, conflicts_with_all = ["verify", "dry_run"])]
+    compare_png: bool,
+    #[clap(flatten)]
+    decode_options: DecodeOptions,
+    /// Convert the given mipmap level instead of the base level
+    #[clap(long, default_value_t = 0)]
+    mip: usize,
+    /// Also write the opaque texel bitmap as a black and white PNG
+    #[clap(long)]
+    emit_bitmap: Option<PathBuf>,
+    /// Crop fully transparent borders, printing the kept region as `<output> <x> <y> <width> <height>`
+    #[clap(long)]
+    trim: bool,
+    /// Rotate the image clockwise by 90, 180 or 270 degrees
+    ///
+    /// Transforms are applied in a fixed order: --crop, --trim, --rotate and then
+    /// --resize or --scale-factor, so the crop region refers to the unrotated texture.
+    #[clap(long, value_enum)]
+    rotate: Option<transform::Rotation>,
+    /// Guess the layout of textures with an unknown format from their pixel data size
+    #[clap(long)]
+    guess_format: bool,
+    /// Reject textures declaring a width or height above this before decoding them,
+    /// which keeps crafted files from making batch jobs allocate huge buffers
+    #[clap(long, default_value_t = 16384)]
+    max_dimension: u32,
+    /// Decode textures with an unknown format as if they were in this format
+    #[clap(long, value_enum, conflicts_with = "guess_format")]
+    assume_format: Option<TexFormat>,
+    /// Skip textures in any other format
+    #[clap(long, value_enum)]
+    only_format: Option<TexFormat>,
+    /// Also enter symbolically linked directories and convert linked files when
+    /// walking input directories, directories reached more than once are skipped
+    #[clap(long)]
+    follow_symlinks: bool,
+    /// Only warn about files that don't start with the tex magic instead of rejecting them
+    #[clap(long)]
+    ignore_magic: bool,
+    /// Treat warnings, like an assumed byte order or unexpected bytes in the file, as errors
+    /// and don't write any output for textures which caused them
+    #[clap(long)]
+    strict: bool,
+    /// Convert premultiplied alpha to straight alpha after decoding
+    #[clap(long)]
+    unpremultiply: bool,
+    /// Like --unpremultiply, but only for textures that look premultiplied because
+    /// their colors hardly ever exceed their alpha, see `info`
+    #[clap(long, conflicts_with = "unpremultiply")]
+    auto_unpremultiply: bool,
+    /// Convert colors with a transfer function: `srgb` or a gamma value like `2.2` encode
+    /// linear data, `linear` decodes sRGB data. The PNG is tagged accordingly
+    #[clap(long)]
+    gamma: Option<transform::Gamma>,
+    /// Tag the PNG as sRGB without changing the colors, for textures that already are
+    #[clap(long, conflicts_with = "gamma")]
+    srgb: bool,
+    /// Resample the image to `<width>x<height>` before writing it, leave out either side
+    /// (`256x` or `x256`) to keep the aspect ratio
+    #[clap(long)]
+    resize: Option<resize::Target>,
+    /// Resample the image by a factor, e.g. `0.5` for half the size
+    #[clap(long, conflicts_with = "resize", value_parser = parse_scale_factor)]
+    scale_factor: Option<f64>,
+    /// Filter used by --resize and --scale-factor
+    #[clap(long, value_enum, default_value_t = resize::Filter::Triangle)]
+    filter: resize::Filter,
+    /// Record the format, version and scale of the texture in `siltex:format`,
+    /// `siltex:version` and `siltex:scale` text chunks of the PNG, which png2tex uses to
+    /// restore the header
+    ///
+    /// The scale is also stored as pixels per unspecified unit in the pHYs chunk.
+    #[clap(long)]
+    embed_metadata: bool,
+    #[clap(flatten)]
+    png_options: PngOptions,
+    /// Make pixels of this `RRGGBB` color transparent, for textures predating alpha channels
+    #[clap(long)]
+    colorkey: Option<transform::Rgb>,
+    /// Largest difference per channel from the --colorkey color that still counts as a match
+    #[clap(long, default_value_t = 0, requires = "colorkey")]
+    colorkey_tolerance: u8,
+    /// Make alpha values of at least this cutoff fully opaque and all others fully
+    /// transparent, for clean masks
+    #[clap(long)]
+    alpha_threshold: Option<u8>,
+    /// Fill the color of fully transparent pixels with that of the nearest visible one,
+    /// like `png2tex --bleed`
+    #[clap(long)]
+    bleed: bool,
+    /// Write an indexed PNG with at most this many colors, picked with median cut
+    ///
+    /// Much smaller for sprites and icons with few colors, whose colors are kept exactly.
+    #[clap(
+        long,
+        conflicts_with = "depth",
+        value_parser = clap::value_parser!(u16).range(1..=256)
+    )]
+    palette: Option<u16>,
+    /// Permute the decoded RGBA channels, e.g. `argb`; `0` and `1` select constant 0x00 and 0xFF
+    #[clap(long)]
+    swizzle: Option<transform::Swizzle>,
+    /// Write the alpha channel as a grayscale image instead, and the image composited
+    /// over magenta to `<name>.magenta.png`, for spotting bad masks
+    #[clap(
+        long,
+        conflicts_with_all = ["mipmaps", "slice", "cell", "atlas", "outputs"]
+    )]
+    alpha_preview: bool,
+    /// Only convert the region given as `<x>,<y>,<width>,<height>`
+    #[clap(long)]
+    crop: Option<transform::Rect>,
+    /// Slice the image into a `<columns>x<rows>` grid of frames and write them as an animated PNG
+    ///
+    /// Frames are played in row-major order, pixels that don't fill a whole cell are dropped.
+    #[clap(long)]
+    frames: Option<transform::Grid>,
+    /// Frame rate of the animation written with --frames or --apng
+    #[clap(long, default_value_t = 10, value_parser = clap::value_parser!(u16).range(1..))]
+    fps: u16,
+    /// Convert every mipmap level, each to its own `<name>.mip<level>.png` file
+    #[clap(
+        long,
+        conflicts_with_all = ["mip", "crop", "trim", "rotate", "resize", "scale_factor", "frames", "slice", "cell"]
+    )]
+    mipmaps: bool,
+    /// Write the levels converted with --mipmaps as the frames of a single animated PNG instead
+    #[clap(long, requires = "mipmaps")]
+    apng: bool,
+    /// Cut the image into a `<columns>x<rows>` grid and write every cell to its own
+    /// `<name>_<row>_<column>.png` file
+    #[clap(long, group = "cells", conflicts_with_all = ["frames", "cell"])]
+    slice: Option<transform::Grid>,
+    /// Like --slice, but with cells of a fixed `<width>x<height>` size
+    #[clap(long, group = "cells", conflicts_with = "frames")]
+    cell: Option<resize::Size>,
+    /// Strip this many pixels from every edge of the cells of --slice or --cell, for
+    /// atlases with gutters between their sprites
+    #[clap(long, default_value_t = 0, requires = "cells")]
+    cell_padding: u32,
+    /// Extract the sprites named in a JSON atlas descriptor, an array of objects like
+    /// `{"name": "hero/idle", "x": 0, "y": 0, "w": 32, "h": 48}`, each to
+    /// `<output name>/<sprite name>.png`
+    ///
+    /// TexturePacker property lists for cocos2d work as well, their rotated frames are
+    /// turned upright and trimmed ones restored to their original size.
+    ///
+    /// The descriptor's coordinates refer to the texture as stored, so the options
+    /// transforming it first, like --crop or --rotate, can't be combined with it.
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "slice", "cell", "frames", "mipmaps", "outputs",
+            "crop", "trim", "rotate", "resize", "scale_factor",
+        ]
+    )]
+    atlas: Option<PathBuf>,
+    /// Treat the texture as a cube map and write its six faces to `<name>_posx.png`,
+    /// `<name>_negx.png` and so on, see `info` for where the faces are
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "atlas", "slice", "cell", "frames", "mipmaps", "outputs", "alpha_preview",
+            "crop", "trim", "rotate", "resize", "scale_factor",
+        ]
+    )]
+    cubemap: bool,
+    /// Only write this face of a --cubemap, to the output path itself
+    #[clap(long, requires = "cubemap", allow_hyphen_values = true)]
+    face: Option<cubemap::Face>,
+    /// How the faces of a --cubemap are arranged
+    #[clap(long, value_enum, default_value_t = cubemap::Layout::Strip, requires = "cubemap")]
+    cube_layout: cubemap::Layout,
+}
+
+/// Settings affecting how pixel data is converted to RGBA.
+#[derive(clap::Args)]
+struct DecodeOptions {
+    /// How to show alpha-only (A8) textures
+    #[clap(long = "a8-as", value_enum, default_value_t = AlphaExpansion::White)]
+    a8_as: AlphaExpansion,
+    /// Expand single-channel (A8, L8) and luminance-alpha (LA88) textures to RGBA instead of
+    /// writing grayscale PNGs with or without alpha
+    ///
+    /// The options that only work on RGBA, like --swizzle or --palette, always expand them.
+    #[clap(long)]
+    expand_rgba: bool,
+    /// Byte order of formats packing a pixel into 16 bits (5551, 4444), defaults to little
+    #[clap(long, value_enum)]
+    pixel_endian: Option<PixelEndian>,
+}
+
+/// Encoder settings for written PNG files.
+///
+/// Every setting has a fixed default, matching what the `png` crate used when they
+/// were pinned, and no timestamp (`tIME`) chunk is written, so converting the same
+/// texture with the same options always produces a byte-identical PNG.
+#[derive(clap::Args)]
+struct PngOptions {
+    /// Compression level: `fast`, `default`, `best` or 0-9, where 0-3 mean fast, 4-6 default
+    /// and 7-9 best. Defaults to `fast`
+    #[clap(
+        long,
+        visible_alias = "compression",
+        value_parser = parse_png_compression,
+        default_value = "fast"
+    )]
+    png_compression: png::Compression,
+    /// Filter applied to each row before compression
+    #[clap(long, value_enum, default_value_t = PngFilter::Sub)]
+    png_filter: PngFilter,
+    /// Bits per channel of the written image, 16-bit channels replicate the decoded 8-bit value.
+    ///
+    /// Formats with 16-bit channels are always written with 16 bits per channel.
+    #[clap(long, value_enum, default_value_t = PngDepth::Eight)]
+    depth: PngDepth,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum OutputFormat {
+    Png,
+    /// Windows icon
+    Ico,
+    /// Quantized to at most 256 colors with binary transparency, animated with --frames
+    Gif,
+    /// Netpbm RGB image, without alpha
+    Ppm,
+    /// Netpbm image keeping alpha and grayscale
+    Pam,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum PngDepth {
+    #[value(name = "8")]
+    Eight,
+    #[value(name = "16")]
+    Sixteen,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum PngFilter {
+    None,
+    Sub,
+    Up,
+    Avg,
+    Paeth,
+    /// Pick the best filter for every row
+    Adaptive,
+}
+
+fn parse_scale_factor(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(factor) if factor > 0.0 && factor.is_finite() => Ok(factor),
+        Ok(_) => Err("scale factor must be positive".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_time(value: &str) -> Result<SystemTime, String> {
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Ok(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds));
+    }
+
+    let invalid = || format!("expected seconds or a date like 2024-05-01T12:30:00, got {value}");
+    let (date, time) = value.split_once('T').unwrap_or((value, "00:00:00"));
+    let parse_fields = |text: &str, separator| {
+        text.split(separator)
+            .map(|field: &str| field.parse::<u64>().ok())
+            .collect::<Option<Vec<_>>>()
+    };
+    let (Some(&[year, month, day]), Some(time)) =
+        (parse_fields(date, '-').as_deref(), parse_fields(time, ':'))
+    else {
+        return Err(invalid());
+    };
+    let (hour, minute, second) = match time[..] {
+        [hour, minute] => (hour, minute, 0),
+        [hour, minute, second] => (hour, minute, second),
+        _ => return Err(invalid()),
+    };
+    if year < 1970 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(invalid());
+    }
+    if hour > 23 || minute > 59 || second > 59 {
+        return Err(invalid());
+    }
+
+    // Days since the epoch of the proleptic Gregorian calendar, counting years
+    // from March so that leap days come last.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    let seconds = days * 86400 + hour * 3600 + minute * 60 + second;
+    Ok(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+fn parse_png_compression(value: &str) -> Result<png::Compression, String> {
+    Ok(match value {
+        "fast" => png::Compression::Fast,
+        "default" => png::Compression::Default,
+        "best" => png::Compression::Best,
+        _ => match value.parse::<u8>() {
+            Ok(0..=3) => png::Compression::Fast,
+            Ok(4..=6) => png::Compression::Default,
+            Ok(7..=9) => png::Compression::Best,
+            _ => return Err("expected fast, default, best or a level between 0 and 9".to_string()),
+        },
+    })
+}
+
+impl PngOptions {
+    fn apply<W: std::io::Write>(&self, encoder: &mut png::Encoder<W>) {
+        encoder.set_depth(match self.depth {
+            PngDepth::Eight => png::BitDepth::Eight,
+            PngDepth::Sixteen => png::BitDepth::Sixteen,
+        });
+        encoder.set_compression(self.png_compression);
+
+        match self.png_filter {
+            PngFilter::Adaptive => encoder.set_adaptive_filter(png::AdaptiveFilterType::Adaptive),
+            filter => {
+                encoder.set_adaptive_filter(png::AdaptiveFilterType::NonAdaptive);
+                encoder.set_filter(match filter {
+                    PngFilter::None => png::FilterType::NoFilter,
+                    PngFilter::Sub => png::FilterType::Sub,
+                    PngFilter::Up => png::FilterType::Up,
+                    PngFilter::Avg => png::FilterType::Avg,
+                    PngFilter::Paeth => png::FilterType::Paeth,
+                    PngFilter::Adaptive => unreachable!(),
+                })
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum AlphaExpansion {
+    /// White pixels with the stored value as their alpha
+    White,
+    /// Opaque grayscale pixels with the stored value as their intensity
+    Gray,
+}
+
+/// Byte order of pixels packed into 16-bit values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+enum PixelEndian {
+    #[default]
+    Little,
+    Big,
+}
+
+impl PixelEndian {
+    fn read_u16(self, bytes: [u8; 2]) -> u16 {
+        match self {
+            PixelEndian::Little => u16::from_le_bytes(bytes),
+            PixelEndian::Big => u16::from_be_bytes(bytes),
+        }
+    }
+
+    fn write_u16(self, value: u16) -> [u8; 2] {
+        match self {
+            PixelEndian::Little => value.to_le_bytes(),
+            PixelEndian::Big => value.to_be_bytes(),
+        }
+    }
+}
+
+const MAGIC: [u8; 4] = *b"TEX\n";
+
+// Settings of the command line interface, set by `run` and `bench`. The public
+// decoding functions don't read them, so they behave the same in any process.
+
+/// Set by `tex2png --strict` to report warnings as errors.
+static STRICT: AtomicBool = AtomicBool::new(false);
+/// How many times `-v` was given.
+static VERBOSITY: AtomicU8 = AtomicU8::new(0);
+/// Set when converting a single file with `--progress` to report how many rows were decoded.
+static ROW_PROGRESS: AtomicBool = AtomicBool::new(false);
+/// Number of threads to decode a large texture on, set when converting a single file.
+static DECODE_THREADS: AtomicUsize = AtomicUsize::new(1);
+/// Smallest number of pixels worth decoding on several threads.
+const PARALLEL_DECODE_PIXELS: usize = 512 * 512;
+
+thread_local! {
+    /// Number of warnings printed since the texture converted on this thread
+    /// started being converted.
+    static WARNINGS: Cell<usize> = const { Cell::new(0) };
+    /// Lines printed by the conversion running on this thread while inside of [`capture`].
+    static CAPTURED: RefCell<Option<Vec<(Stream, String)>>> = const { RefCell::new(None) };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stream {
+    Stdout,
+    Stderr,
+}
+
+fn print_line(stream: Stream, message: std::fmt::Arguments) {
+    CAPTURED.with_borrow_mut(|captured| match captured {
+        Some(lines) => lines.push((stream, message.to_string())),
+        None => match stream {
+            Stream::Stdout => println!("{message}"),
+            Stream::Stderr => eprintln!("{message}"),
+        },
+    });
+}
+
+/// Runs `f`, collecting the lines printed by it instead of printing them.
+fn capture<T>(f: impl FnOnce() -> T) -> (T, Vec<(Stream, String)>) {
+    CAPTURED.set(Some(Vec::new()));
+    let result = f();
+    (result, CAPTURED.take().unwrap_or_default())
+}
+
+/// Prints lines collected by [`capture`].
+fn replay(lines: Vec<(Stream, String)>) {
+    for (stream, line) in lines {
+        print_line(stream, format_args!("{line}"));
+    }
+}
+
+fn print_warning(message: std::fmt::Arguments) {
+    WARNINGS.set(WARNINGS.get() + 1);
+    if STRICT.load(atomic::Ordering::Relaxed) {
+        print_line(Stream::Stderr, format_args!("error: {message}"));
+    } else {
+        print_line(Stream::Stderr, format_args!("warning: {message}"));
+    }
+}
+
+/// Problems with the contents of a tex file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TexError {
+    /// The file is shorter than a header.
+    TooShort,
+    /// The file doesn't start with the tex magic, contains the bytes found instead.
+    Magic([u8; 4]),
+    /// The header has a version other than 2.
+    Version(u8),
+    /// The format byte of the header doesn't name a known format.
+    UnknownFormat(u8),
+    /// Pixel data in this format can't be decoded.
+    Unsupported(TexFormat),
+    /// The named region extends past the end of the file, or its end can't be
+    /// represented on this platform.
+    Truncated(&'static str),
+}
+
+impl std::fmt::Display for TexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TexError::TooShort => write!(f, "file is too short to be a tex file"),
+            TexError::Magic(magic) => write!(
+                f,
+                "mismatched magic, found {}",
+                magic.map(|byte| format!("{byte:02X}")).join(" ")
+            ),
+            TexError::Version(version) => write!(f, "unsupported tex file version: {version}"),
+            TexError::UnknownFormat(format) => {
+                write!(f, "unsupported texture format: 0x{format:02X}")
+            }
+            TexError::Unsupported(format) => {
+                write!(f, "conversion from {format:?} is not implemented yet")
+            }
+            TexError::Truncated(region) => write!(f, "{region} extends past the end of the file"),
+        }
+    }
+}
+
+impl std::error::Error for TexError {}
+
+/// Converts an offset and size from a header into a range, without wrapping
+/// around on targets where `usize` is 32 bits.
+fn checked_region(offset: i32, size: i32, tex_len: usize) -> Option<std::ops::Range<usize>> {
+    let start = usize::try_from(offset).ok()?;
+    let end = start.checked_add(usize::try_from(size).ok()?)?;
+    (end <= tex_len).then_some(start..end)
+}
+
+struct TexHeader {
+    magic: [u8; 4],
+    version: u8,
+    format: u8,
+    mipmaps: u8,
+    opaque_bitmap: u8,
+    width: i16,
+    height: i16,
+    scale: i32,
+    pixels_offset: i32,
+    pixels_size: i32,
+    bitmap_offset: i32,
+    bitmap_size: i32,
+}
+
+impl TexHeader {
+    /// Size of the version 2 header in bytes, all header lengths are derived from this.
+    const SIZE: usize = 32;
+
+    /// Range of the pixel data within a texture that is `tex_len` bytes long.
+    fn pixels_range(&self, tex_len: usize) -> Result<std::ops::Range<usize>, TexError> {
+        checked_region(self.pixels_offset, self.pixels_size, tex_len)
+            .ok_or(TexError::Truncated("pixel data"))
+    }
+
+    /// Range of the opaque bitmap within a texture that is `tex_len` bytes long.
+    fn bitmap_range(&self, tex_len: usize) -> Result<std::ops::Range<usize>, TexError> {
+        checked_region(self.bitmap_offset, self.bitmap_size, tex_len)
+            .ok_or(TexError::Truncated("opaque bitmap"))
+    }
+
+    /// Byte ranges of the header itself and of the regions it declares, relative to its start.
+    fn regions(&self) -> impl Iterator<Item = (&'static str, std::ops::Range<usize>)> {
+        let region = |offset: i32, size: i32| {
+            offset as usize..(offset as usize).saturating_add(size as usize)
+        };
+        [
+            ("header", 0..Self::SIZE),
+            ("pixel data", region(self.pixels_offset, self.pixels_size)),
+            ("bitmap", region(self.bitmap_offset, self.bitmap_size)),
+        ]
+        .into_iter()
+        .filter(|(_, region)| !region.is_empty())
+    }
+
+    /// Parses the header at the start of `tex`, returns `None` if it is too short.
+    fn parse(tex: &[u8]) -> Option<TexHeader> {
+        if tex.len() < Self::SIZE {
+            return None;
+        }
+
+        Some(TexHeader {
+            magic: tex[..4].try_into().unwrap(),
+            version: tex[4],
+            format: tex[5],
+            mipmaps: tex[6],
+            opaque_bitmap: tex[7],
+            width: i16::from_be_bytes(tex[8..10].try_into().unwrap()),
+            height: i16::from_be_bytes(tex[10..12].try_into().unwrap()),
+            scale: i32::from_be_bytes(tex[12..16].try_into().unwrap()),
+            pixels_offset: i32::from_be_bytes(tex[16..20].try_into().unwrap()),
+            pixels_size: i32::from_be_bytes(tex[20..24].try_into().unwrap()),
+            bitmap_offset: i32::from_be_bytes(tex[24..28].try_into().unwrap()),
+            bitmap_size: i32::from_be_bytes(tex[28..32].try_into().unwrap()),
+        })
+    }
+
+    fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[..4].copy_from_slice(&self.magic);
+        bytes[4] = self.version;
+        bytes[5] = self.format;
+        bytes[6] = self.mipmaps;
+        bytes[7] = self.opaque_bitmap;
+        bytes[8..10].copy_from_slice(&self.width.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.height.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.scale.to_be_bytes());
+        bytes[16..20].copy_from_slice(&self.pixels_offset.to_be_bytes());
+        bytes[20..24].copy_from_slice(&self.pixels_size.to_be_bytes());
+        bytes[24..28].copy_from_slice(&self.bitmap_offset.to_be_bytes());
+        bytes[28..32].copy_from_slice(&self.bitmap_size.to_be_bytes());
+        bytes
+    }
+}
+
+/// Location of a single level of a mipmap chain inside the pixel region.
+#[derive(Debug, Clone, Copy)]
+struct MipLevel {
+    width: usize,
+    height: usize,
+    offset: usize,
+    size: usize,
+}
+
+/// Number of levels in a full mipmap chain going down to 1x1.
+fn full_mip_count(width: usize, height: usize) -> usize {
+    (usize::BITS - width.max(height).max(1).leading_zeros()) as usize
+}
+
+/// Computes the layout of the first `count` mipmap levels.
+///
+/// Levels are stored back to back starting with the base level, each one half the
+/// size of the previous one (rounded down, but at least one pixel).
+fn mip_levels(format: TexFormat, width: usize, height: usize, count: usize) -> Vec<MipLevel> {
+    let mut levels = Vec::with_capacity(count);
+    let mut offset = 0;
+    for level in 0..count {
+        let width = (width >> level).max(1);
+        let height = (height >> level).max(1);
+        let size = format.expected_pixel_size(width, height);
+        levels.push(MipLevel {
+            width,
+            height,
+            offset,
+            size,
+        });
+        offset += size;
+    }
+    levels
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TexFormat {
+    A8 = 0x01,
+    L8 = 0x02,
+    La88 = 0x03,
+    /// Opaque BGR with a byte per channel, written as an RGB PNG unless --expand-rgba is given
+    Bgr888 = 0x07,
+    Bgra8888 = 0x08,
+    Bgra5551 = 0x0A,
+    Bgra4444 = 0x0B,
+    Rgba4444 = 0x0C,
+    Rgba16 = 0x0D,
+    Pvrtc2Rgba = 0x84,
+    Pvrtc4Rgba = 0x85,
+    Pvrtc2Rgb = 0x86,
+    Pvrtc4Rgb = 0x87,
+    Etc2Rgb = 0x88,
+    Etc2Rgba = 0x89,
+}
+
+impl TexFormat {
+    pub fn from_value(value: u8) -> Option<TexFormat> {
+        Some(match value {
+            0x01 => TexFormat::A8,
+            0x02 => TexFormat::L8,
+            0x03 => TexFormat::La88,
+            0x07 => TexFormat::Bgr888,
+            0x08 => TexFormat::Bgra8888,
+            0x0A => TexFormat::Bgra5551,
+            0x0B => TexFormat::Bgra4444,
+            0x0C => TexFormat::Rgba4444,
+            0x0D => TexFormat::Rgba16,
+            0x84 => TexFormat::Pvrtc2Rgba,
+            0x85 => TexFormat::Pvrtc4Rgba,
+            0x86 => TexFormat::Pvrtc2Rgb,
+            0x87 => TexFormat::Pvrtc4Rgb,
+            0x88 => TexFormat::Etc2Rgb,
+            0x89 => TexFormat::Etc2Rgba,
+            _ => return None,
+        })
+    }
+
+    /// Name of the format as accepted by `--format`, like `bgra8888` or `pvrtc4-rgb`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TexFormat::A8 => "a8",
+            TexFormat::L8 => "l8",
+            TexFormat::La88 => "la88",
+            TexFormat::Bgr888 => "bgr888",
+            TexFormat::Bgra8888 => "bgra8888",
+            TexFormat::Bgra5551 => "bgra5551",
+            TexFormat::Bgra4444 => "bgra4444",
+            TexFormat::Rgba4444 => "rgba4444",
+            TexFormat::Rgba16 => "rgba16",
+            TexFormat::Pvrtc2Rgba => "pvrtc2-rgba",
+            TexFormat::Pvrtc4Rgba => "pvrtc4-rgba",
+            TexFormat::Pvrtc2Rgb => "pvrtc2-rgb",
+            TexFormat::Pvrtc4Rgb => "pvrtc4-rgb",
+            TexFormat::Etc2Rgb => "etc2-rgb",
+            TexFormat::Etc2Rgba => "etc2-rgba",
+        }
+    }
+
+    /// Guesses the most likely format of a texture from the size of its pixel data.
+    pub fn guess(width: usize, height: usize, pixels_size: usize) -> Option<TexFormat> {
+        Some(match pixels_size.checked_div(width * height)? {
+            1 => TexFormat::A8,
+            2 => TexFormat::Bgra5551,
+            3 => TexFormat::Bgr888,
+            4 => TexFormat::Bgra8888,
+            8 => TexFormat::Rgba16,
+            _ => return None,
+        })
+    }
+
+    /// Whether pixel data in this format can be converted to RGBA, which depends
+    /// on the codecs enabled at build time.
+    pub fn is_decodable(self) -> bool {
+        match self {
+            TexFormat::Etc2Rgb | TexFormat::Etc2Rgba => cfg!(feature = "etc"),
+            _ if self.is_pvrtc() => cfg!(feature = "pvrtc"),
+            _ => true,
+        }
+    }
+
+    /// Whether RGBA images can be converted to pixel data in this format.
+    pub fn is_encodable(self) -> bool {
+        self.is_decodable()
+            && !matches!(
+                self,
+                TexFormat::Etc2Rgb | TexFormat::Etc2Rgba | TexFormat::Rgba16
+            )
+            && !self.is_pvrtc()
+    }
+
+    /// Whether channels are stored as 16-bit values and decode to 16-bit samples.
+    pub fn is_16_bit(self) -> bool {
+        self == TexFormat::Rgba16
+    }
+
+    /// Whether every pixel is packed into a 16-bit value, whose byte order isn't
+    /// recorded in the file.
+    pub fn is_packed_16(self) -> bool {
+        matches!(
+            self,
+            TexFormat::Bgra5551 | TexFormat::Bgra4444 | TexFormat::Rgba4444
+        )
+    }
+
+    /// Number of bytes every pixel occupies, `None` for block compressed formats.
+    pub fn bytes_per_pixel(self) -> Option<usize> {
+        Some(match self {
+            TexFormat::A8 | TexFormat::L8 => 1,
+            TexFormat::La88 | TexFormat::Bgra5551 | TexFormat::Bgra4444 | TexFormat::Rgba4444 => 2,
+            TexFormat::Bgr888 => 3,
+            TexFormat::Bgra8888 => 4,
+            TexFormat::Rgba16 => 8,
+            _ => return None,
+        })
+    }
+
+    /// Width and height in pixels and size in bytes of the blocks of block
+    /// compressed formats, `None` for all others.
+    pub fn block_size(self) -> Option<(usize, usize, usize)> {
+        Some(match self {
+            // PVRTC stores 64-bit blocks of 8x4 (2bpp) or 4x4 (4bpp) pixels.
+            TexFormat::Pvrtc2Rgba | TexFormat::Pvrtc2Rgb => (8, 4, 8),
+            TexFormat::Pvrtc4Rgba | TexFormat::Pvrtc4Rgb => (4, 4, 8),
+            // ETC2 stores 4x4 blocks of 64 bits, twice that with EAC alpha.
+            TexFormat::Etc2Rgb => (4, 4, 8),
+            TexFormat::Etc2Rgba => (4, 4, 16),
+            _ => return None,
+        })
+    }
+
+    /// Number of bits each pixel occupies in the pixel region.
+    ///
+    /// For block compressed formats this is the average over a whole block.
+    pub fn bits_per_pixel(self) -> usize {
+        match (self.bytes_per_pixel(), self.block_size()) {
+            (Some(bytes), _) => bytes * 8,
+            (None, Some((width, height, bytes))) => bytes * 8 / (width * height),
+            (None, None) => unreachable!("{self:?} has neither pixels nor blocks"),
+        }
+    }
+
+    /// Number of bytes needed to store a `width` by `height` image in this format.
+    pub fn expected_pixel_size(self, width: usize, height: usize) -> usize {
+        let Some((block_width, block_height, block_bytes)) = self.block_size() else {
+            return width * height * self.bytes_per_pixel().unwrap();
+        };
+        let (mut blocks_wide, mut blocks_high) =
+            (width.div_ceil(block_width), height.div_ceil(block_height));
+        if self.is_pvrtc() {
+            // PVRTC textures always consist of at least 2x2 blocks.
+            (blocks_wide, blocks_high) = (blocks_wide.max(2), blocks_high.max(2));
+        }
+        blocks_wide * blocks_high * block_bytes
+    }
+
+    fn is_pvrtc(self) -> bool {
+        matches!(
+            self,
+            TexFormat::Pvrtc2Rgba
+                | TexFormat::Pvrtc4Rgba
+                | TexFormat::Pvrtc2Rgb
+                | TexFormat::Pvrtc4Rgb
+        )
+    }
+}
+
+impl From<TexFormat> for u8 {
+    /// The value recording `format` in the header, the inverse of [`TexFormat::from_value`].
+    fn from(format: TexFormat) -> u8 {
+        format as u8
+    }
+}
+
+/// Expands a 4-bit channel value to 8 bits by bit replication, mapping 0x0 to 0x00
+/// and 0xF to 0xFF.
+fn expand_nibble(value: u16) -> u8 {
+    let value = (value & 0xF) as u8;
+    (value << 4) | value
+}
+
+/// Returns the part of the pixel region `pixels` that holds mipmap level `index`
+/// of a texture with `levels`.
+///
+/// The region is expected to hold every level, back to back. A region larger than
+/// that is accepted with a warning since files with padding legitimately contain
+/// more data than their pixels.
+fn take_pixels<'a>(
+    pixels: &'a mut [u8],
+    levels: &[MipLevel],
+    index: usize,
+) -> Option<&'a mut [u8]> {
+    let level = levels[index];
+    let last = levels.last().unwrap();
+    let expected = last.offset + last.size;
+    if pixels.len() < level.offset + level.size {
+        if index == 0 {
+            message!(
+                "Pixel data is too short: expected {} bytes but got {}",
+                level.size,
+                pixels.len()
+            );
+        } else {
+            message!("Pixel data is too short to contain mipmap level {index}");
+        }
+        return None;
+    }
+    if pixels.len() > expected {
+        let mipmaps = match levels.len() {
+            1 => String::new(),
+            count => format!(" for {count} mipmap levels"),
+        };
+        warning!(
+            "Pixel data is larger than expected: expected {expected} bytes{mipmaps} but got {}, ignoring the remaining {} bytes",
+            pixels.len(),
+            pixels.len() - expected
+        );
+    }
+    Some(&mut pixels[level.offset..level.offset + level.size])
+}
+
+/// Recursively collects all `.tex` files inside `dir` in a stable order.
+///
+/// Symbolic links are skipped unless `follow_symlinks` is set, in which case every
+/// directory is only entered once so that links pointing back up the tree don't
+/// make the walk loop forever.
+fn find_tex_files(
+    dir: &Path,
+    follow_symlinks: bool,
+    visited: &mut HashSet<PathBuf>,
+    files: &mut Vec<PathBuf>,
+) -> std::io::Result<()> {
+    if follow_symlinks && !visited.insert(dir.canonicalize()?) {
+        warning!("Skipping {}, which was already visited", dir.display());
+        return Ok(());
+    }
+
+    let mut entries = std::fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        let mut file_type = entry.file_type()?;
+        if file_type.is_symlink() && follow_symlinks {
+            match std::fs::metadata(&path) {
+                Ok(metadata) => file_type = metadata.file_type(),
+                Err(error) => {
+                    warning!("Skipping broken link {}: {error}", path.display());
+                    continue;
+                }
+            }
+        }
+
+        if file_type.is_dir() {
+            find_tex_files(&path, follow_symlinks, visited, files)?;
+        } else if file_type.is_file() && path.extension().is_some_and(|e| e == "tex") {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+impl Tex2Png {
+    /// Whether the output path names a directory for the converted files rather
+    /// than a single file, which is the case for directory or multiple inputs.
+    fn outputs_to_directory(&self) -> bool {
+        self.output_template.is_some()
+            || self.tex_paths.len() > 1
+            || self
+                .tex_paths
+                .iter()
+                .any(|path| path.is_dir() || zip::is_archive(path))
+    }
+
+    /// Whether the output path of a single texture names the directory to place
+    /// it in rather than the file to write.
+    fn output_is_directory(&self) -> bool {
+        self.output_path.as_ref().is_some_and(|path| {
+            path.is_dir()
+                || path
+                    .as_os_str()
+                    .to_string_lossy()
+                    .ends_with(std::path::is_separator)
+        })
+    }
+
+    /// Format of the file written to `path`.
+    fn output_format_for(&self, path: &Path) -> OutputFormat {
+        self.output_format
+            .unwrap_or_else(|| match path.extension().and_then(|e| e.to_str()) {
+                Some(e) if e.eq_ignore_ascii_case("ico") => OutputFormat::Ico,
+                Some(e) if e.eq_ignore_ascii_case("gif") => OutputFormat::Gif,
+                Some(e) if e.eq_ignore_ascii_case("ppm") => OutputFormat::Ppm,
+                Some(e) if e.eq_ignore_ascii_case("pam") => OutputFormat::Pam,
+                _ => OutputFormat::Png,
+            })
+    }
+
+    /// Extension of output files named after their texture.
+    fn output_extension(&self) -> &'static str {
+        match self.output_format {
+            Some(OutputFormat::Ico) => "ico",
+            Some(OutputFormat::Gif) => "gif",
+            Some(OutputFormat::Ppm) => "ppm",
+            Some(OutputFormat::Pam) => "pam",
+            Some(OutputFormat::Png) | None => "png",
+        }
+    }
+
+    /// Whether options that only work on RGBA images are used, for which
+    /// single-channel textures are expanded to RGBA.
+    fn needs_rgba(&self) -> bool {
+        self.colorkey.is_some()
+            || self.unpremultiply
+            || self.auto_unpremultiply
+            || self.bleed
+            || self.swizzle.is_some()
+            || self.palette.is_some()
+    }
+
+    fn create_dirs(&self) -> bool {
+        self.create_dirs.unwrap_or_else(|| {
+            self.outputs_to_directory() || self.output_is_directory() || self.atlas.is_some()
+        })
+    }
+}
+
+/// Resolves the input and output paths of every conversion requested by `tex2png`.
+///
+/// A directory input is converted recursively into the output directory (or the
+/// current directory), mirroring its structure. Fails with the exit code to report
+/// after printing why if the paths can't be resolved.
+fn conversion_jobs(tex2png: &Tex2Png) -> Result<Vec<(PathBuf, PathBuf)>, ExitCode> {
+    if !tex2png.outputs_to_directory() {
+        let tex_path = &tex2png.tex_paths[0];
+        let derived_name = || {
+            if tex_path.extension().is_some_and(|e| e == "tex") {
+                Ok(tex_path
+                    .strip_prefix(tex_path.parent().unwrap())
+                    .unwrap()
+                    .with_extension(tex2png.output_extension()))
+            } else {
+                message!("No output file name provided and tex path doesn't have .tex extension");
+                Err(ExitCode::from(Failure::Usage))
+            }
+        };
+        let out_path = match &tex2png.output_path {
+            Some(dir) if tex2png.output_is_directory() => dir.join(derived_name()?),
+            Some(path) => path.clone(),
+            None => derived_name()?,
+        };
+
+        if let Some(path) = tex2png.outputs.iter().find(|path| {
+            tex2png.output_format.is_none()
+                && !path.extension().and_then(|e| e.to_str()).is_some_and(|e| {
+                    ["png", "ico", "gif", "ppm", "pam"]
+                        .iter()
+                        .any(|ext| e.eq_ignore_ascii_case(ext))
+                })
+        }) {
+            message!(
+                "Only PNG, ICO, GIF, PPM and PAM files can be written, unsupported output {}",
+                path.display()
+            );
+            return Err(Failure::Usage.into());
+        }
+
+        return Ok(vec![(tex_path.clone(), out_path)]);
+    }
+    if !tex2png.outputs.is_empty() {
+        message!("--outputs can only be used when converting a single texture");
+        return Err(Failure::Usage.into());
+    }
+
+    let out_dir = tex2png.output_path.as_deref().unwrap_or(Path::new(""));
+    if out_dir.is_file() {
+        message!(
+            "Output path {} must be a directory when converting multiple files",
+            out_dir.display()
+        );
+        return Err(Failure::Usage.into());
+    }
+
+    let mut jobs = Vec::new();
+    for tex_path in &tex2png.tex_paths {
+        if tex_path.is_dir() {
+            let mut files = Vec::new();
+            let mut visited = HashSet::new();
+            if let Err(error) =
+                find_tex_files(tex_path, tex2png.follow_symlinks, &mut visited, &mut files)
+            {
+                message!("Failed to read directory {}: {error}", tex_path.display());
+                return Err(Failure::Io.into());
+            }
+
+            jobs.extend(files.into_iter().map(|path| {
+                let out_path = out_dir
+                    .join(path.strip_prefix(tex_path).unwrap())
+                    .with_extension(tex2png.output_extension());
+                (path, out_path)
+            }));
+        } else if zip::is_archive(tex_path) {
+            let archive = match zip::ZipArchive::open(tex_path) {
+                Ok(archive) => archive,
+                Err(error) => {
+                    message!("Failed to read archive {}: {error}", tex_path.display());
+                    return Err(Failure::Io.into());
+                }
+            };
+
+            let mut names = archive
+                .file_names()
+                .filter(|name| name.ends_with(".tex"))
+                .collect::<Vec<_>>();
+            names.sort();
+            for name in names {
+                if !zip::is_safe_entry_name(name) {
+                    warning!("Skipping archive entry with unsafe path {name:?}");
+                    continue;
+                }
+                let out_path = out_dir
+                    .join(name)
+                    .with_extension(tex2png.output_extension());
+                jobs.push((zip::entry_path(tex_path, name), out_path));
+            }
+        } else if tex_path.extension().is_some_and(|e| e == "tex") {
+            let out_path = out_dir
+                .join(tex_path.file_name().unwrap())
+                .with_extension(tex2png.output_extension());
+            jobs.push((tex_path.clone(), out_path));
+        } else {
+            message!("Input {} doesn't have a .tex extension", tex_path.display());
+            return Err(Failure::Usage.into());
+        }
+    }
+
+    Ok(jobs)
+}
+
+fn list_formats() {
+    let yes_no = |value: bool| if value { "yes" } else { "no" };
+
+    println!(
+        "{:<12} {:<5} {:<11} {:<7} ENCODE",
+        "FORMAT", "BYTE", "BYTES/PIXEL", "DECODE"
+    );
+    for &format in TexFormat::value_variants() {
+        println!(
+            "{:<12} 0x{:02X}  {:<11} {:<7} {}",
+            format.name(),
+            u8::from(format),
+            format.bits_per_pixel() as f64 / 8.0,
+            yes_no(format.is_decodable()),
+            yes_no(format.is_encodable())
+        );
+    }
+}
+
+/// Why a conversion failed, reported as the exit code listed in `--help`.
+///
+/// Textures that don't fit the options given, like a crop region outside of them,
+/// count as malformed. Failures that don't fit any of these exit with the generic
+/// [`ExitCode::FAILURE`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Failure {
+    /// Paths given on the command line that can't be converted, like clap's errors.
+    Usage = 2,
+    Io = 3,
+    Malformed = 4,
+    Unsupported = 5,
+    Encode = 6,
+}
+
+impl From<Failure> for ExitCode {
+    fn from(failure: Failure) -> Self {
+        ExitCode::from(failure as u8)
+    }
+}
+
+/// Records the outcome of one of several conversions in `result`, which keeps
+/// the first failure.
+fn keep_first_failure(result: &mut ExitCode, outcome: ExitCode) {
+    if *result == ExitCode::SUCCESS {
+        *result = outcome;
+    }
+}
+
+/// Runs the command line interface with the arguments of the current process.
+pub fn run() -> ExitCode {
+    let args = Args::parse();
+    VERBOSITY.store(args.verbose, atomic::Ordering::Relaxed);
+
+    let mut tex2png = match args.command {
+        Command::Tex2Png(tex2png) => *tex2png,
+        Command::Info(args) => return info::info(&args),
+        Command::Png2Tex(args) => return png2tex::png2tex(&args),
+        Command::Diff(args) => return diff::diff(&args),
+        Command::Bench(args) => return bench::bench(&args),
+        Command::GenFixture(args) => return fixture::gen_fixture(&args),
+        Command::Formats => {
+            list_formats();
+            return ExitCode::SUCCESS;
+        }
+    };
+
+    STRICT.store(tex2png.strict, atomic::Ordering::Relaxed);
+    tex2png.decode_options.expand_rgba |= tex2png.needs_rgba();
+
+    let jobs = match conversion_jobs(&tex2png) {
+        Ok(jobs) => jobs,
+        Err(code) => return code,
+    };
+    let mut scratch = Scratch::default();
+    if tex2png.watch {
+        watch::watch(
+            jobs,
+            || conversion_jobs(&tex2png),
+            |tex_path, out_path| {
+                convert(tex_path, out_path, &tex2png, &mut scratch) == ExitCode::SUCCESS
+            },
+        );
+    }
+
+    let jobs = skip_unchanged(jobs, &tex2png);
+    let show_progress = !tex2png.dry_run
+        && tex2png
+            .progress
+            .unwrap_or_else(|| std::io::stderr().is_terminal());
+    ROW_PROGRESS.store(show_progress && jobs.len() == 1, atomic::Ordering::Relaxed);
+    let threads = match tex2png.threads {
+        0 => std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+        threads => threads,
+    };
+    if jobs.len() == 1 {
+        DECODE_THREADS.store(threads, atomic::Ordering::Relaxed);
+    }
+    let mut progress =
+        (show_progress && jobs.len() > 1).then(|| progress::Progress::new(jobs.len()));
+
+    if let (Some(progress), Some((tex_path, _))) = (&progress, jobs.first()) {
+        progress.show(tex_path);
+    }
+
+    let mut result = ExitCode::SUCCESS;
+    let mut finished = |index: usize, outcome: ExitCode| {
+        if outcome != ExitCode::SUCCESS {
+            if jobs.len() > 1 {
+                let action = if tex2png.verify { "verify" } else { "convert" };
+                eprintln!("Failed to {action} {}", jobs[index].0.display());
+            }
+            keep_first_failure(&mut result, outcome);
+        }
+        if let Some(progress) = &mut progress {
+            progress.advance();
+            if let Some((tex_path, _)) = jobs.get(index + 1) {
+                progress.show(tex_path);
+            }
+        }
+    };
+
+    if threads > 1 && jobs.len() > 1 {
+        convert_parallel(&jobs, &tex2png, threads.min(jobs.len()), finished);
+    } else if tex2png.queue_depth > 0 && jobs.len() > 1 {
+        let (sender, receiver) = mpsc::sync_channel(tex2png.queue_depth);
+        std::thread::scope(|scope| {
+            scope.spawn(|| read_ahead(&jobs, sender));
+            for (index, read) in receiver {
+                let (tex_path, out_path) = &jobs[index];
+                let outcome =
+                    convert_prefetched(tex_path, out_path, Some(read), &tex2png, &mut scratch);
+                finished(index, outcome);
+            }
+        });
+    } else {
+        for (index, (tex_path, out_path)) in jobs.iter().enumerate() {
+            let outcome = convert(tex_path, out_path, &tex2png, &mut scratch);
+            finished(index, outcome);
+        }
+    }
+    if let Some(progress) = &progress {
+        progress.finish();
+    }
+
+    result
+}
+
+/// When the file was last modified, which is that of the archive for entries of one.
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    let source = zip::split_entry_path(path).map_or(path, |(archive, _)| archive);
+    std::fs::metadata(source).and_then(|m| m.modified()).ok()
+}
+
+/// Removes the jobs that don't need converting: textures not modified since
+/// --since and, in batch conversions without --force, textures older than their
+/// existing output.
+fn skip_unchanged(mut jobs: Vec<(PathBuf, PathBuf)>, tex2png: &Tex2Png) -> Vec<(PathBuf, PathBuf)> {
+    let skip_converted = tex2png.outputs_to_directory() && !tex2png.force && !tex2png.verify;
+    let (mut old, mut converted) = (0, 0);
+    jobs.retain(|(tex_path, out_path)| {
+        // Inputs whose modification time can't be read are always converted.
+        let Some(modified) = modified_time(tex_path) else {
+            return true;
+        };
+        if tex2png.since.is_some_and(|since| modified <= since) {
+            if tex2png.dry_run {
+                output!(
+                    "Would skip {}: not modified since --since",
+                    tex_path.display()
+                );
+            }
+            old += 1;
+            return false;
+        }
+        let up_to_date = skip_converted
+            && std::fs::metadata(out_path)
+                .and_then(|m| m.modified())
+                .is_ok_and(|written| written >= modified);
+        if up_to_date && tex2png.dry_run {
+            output!(
+                "Would skip {}: older than {}",
+                tex_path.display(),
+                out_path.display()
+            );
+        }
+        converted += up_to_date as usize;
+        !up_to_date
+    });
+
+    let plural = |count: usize| if count == 1 { "" } else { "s" };
+    if old > 0 {
+        eprintln!(
+            "Skipping {old} texture{} not modified since --since",
+            plural(old)
+        );
+    }
+    if converted > 0 {
+        eprintln!(
+            "Skipping {converted} texture{} older than their output, use --force to convert them anyway",
+            plural(converted)
+        );
+    }
+    jobs
+}
+
+/// Contents of an input read ahead of its conversion, or why it couldn't be read.
+type Prefetched = Result<Vec<u8>, String>;
+
+/// Reads the inputs of `jobs` in order for --queue-depth and sends them to the
+/// conversions, whose channel holds as many as may be read ahead.
+fn read_ahead(jobs: &[(PathBuf, PathBuf)], sender: mpsc::SyncSender<(usize, Prefetched)>) {
+    for (index, (tex_path, _)) in jobs.iter().enumerate() {
+        let mut tex = Vec::new();
+        let read = zip::read_into(tex_path, &mut tex).map(|()| tex);
+        if sender.send((index, read)).is_err() {
+            break;
+        }
+    }
+}
+
+/// Converts `jobs` on `threads` worker threads, which each decode and write the
+/// files they take, read ahead of them under --queue-depth.
+///
+/// Everything printed during a conversion is held back until all jobs before it
+/// are done, then it is printed and `finished` is called with the job's index and
+/// result, so the output is the same as when converting in order.
+fn convert_parallel(
+    jobs: &[(PathBuf, PathBuf)],
+    tex2png: &Tex2Png,
+    threads: usize,
+    mut finished: impl FnMut(usize, ExitCode),
+) {
+    let next_job = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    let (read_sender, read_receiver) = mpsc::sync_channel(tex2png.queue_depth);
+    let prefetched = (tex2png.queue_depth > 0).then(|| std::sync::Mutex::new(read_receiver));
+    std::thread::scope(|scope| {
+        if prefetched.is_some() {
+            scope.spawn(move || read_ahead(jobs, read_sender));
+        }
+        for _ in 0..threads {
+            let (sender, next_job, prefetched) = (sender.clone(), &next_job, &prefetched);
+            scope.spawn(move || {
+                let mut scratch = Scratch::default();
+                loop {
+                    let (index, read) = match prefetched {
+                        Some(receiver) => match receiver.lock().unwrap().recv() {
+                            Ok((index, read)) => (index, Some(read)),
+                            Err(_) => break,
+                        },
+                        None => (next_job.fetch_add(1, atomic::Ordering::Relaxed), None),
+                    };
+                    let Some((tex_path, out_path)) = jobs.get(index) else {
+                        break;
+                    };
+                    let (result, lines) = capture(|| {
+                        convert_prefetched(tex_path, out_path, read, tex2png, &mut scratch)
+                    });
+                    if sender.send((index, result, lines)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        // Results of jobs that finished before an earlier one.
+        let mut pending = BTreeMap::new();
+        let mut next_finished = 0;
+        for (index, result, lines) in receiver {
+            pending.insert(index, (result, lines));
+            while let Some((result, lines)) = pending.remove(&next_finished) {
+                replay(lines);
+                finished(next_finished, result);
+                next_finished += 1;
+            }
+        }
+    });
+}
+
+/// Buffers kept between conversions so that batch conversions of similarly
+/// sized files don't have to allocate them again for every file.
+#[derive(Default)]
+struct Scratch {
+    tex: Vec<u8>,
+    decoded: Vec<u8>,
+}
+
+fn convert(tex_path: &Path, out_path: &Path, tex2png: &Tex2Png, scratch: &mut Scratch) -> ExitCode {
+    convert_prefetched(tex_path, out_path, None, tex2png, scratch)
+}
+
+/// Like [`convert`], but takes the contents of the input from `prefetched` if it
+/// was read ahead.
+fn convert_prefetched(
+    tex_path: &Path,
+    out_path: &Path,
+    prefetched: Option<Prefetched>,
+    tex2png: &Tex2Png,
+    scratch: &mut Scratch,
+) -> ExitCode {
+    info!("Converting {}", tex_path.display());
+    let start = Instant::now();
+    let read = match prefetched {
+        Some(read) => read.map(|tex| scratch.tex = tex),
+        None => zip::read_into(tex_path, &mut scratch.tex),
+    };
+    if let Err(error) = read {
+        message!("Failed to read {}: {error}", tex_path.display());
+        return Failure::Io.into();
+    }
+    let tex = &mut scratch.tex;
+    let decoded = &mut scratch.decoded;
+    debug!("Read {} bytes in {:.1?}", tex.len(), start.elapsed());
+
+    let textures = texture_offsets(tex);
+    if textures.len() > 1 {
+        info!("Found {} concatenated textures", textures.len());
+    }
+    if textures.len() == 1 {
+        let texture = 0..tex.len();
+        return convert_texture(tex, texture, tex_path, out_path, None, tex2png, decoded);
+    }
+
+    let mut result = ExitCode::SUCCESS;
+    for (i, &start) in textures.iter().enumerate() {
+        let end = textures.get(i + 1).copied().unwrap_or(tex.len());
+        let outcome = convert_texture(
+            tex,
+            start..end,
+            tex_path,
+            out_path,
+            Some(i),
+            tex2png,
+            decoded,
+        );
+        keep_first_failure(&mut result, outcome);
+    }
+
+    result
+}
+
+/// Finds the start of every texture in a file containing multiple concatenated
+/// textures, each header's offsets being relative to the header itself.
+///
+/// The first texture always starts at zero, a following one is only recognized
+/// if its magic starts right after the regions declared by the previous header.
+fn texture_offsets(tex: &[u8]) -> Vec<usize> {
+    let mut offsets = vec![0];
+    let mut start = 0;
+    while let Some(header) = TexHeader::parse(&tex[start..]) {
+        if header.magic != MAGIC {
+            break;
+        }
+
+        let end = start.saturating_add(header.regions().map(|(_, r)| r.end).max().unwrap());
+        if !tex.get(end..).is_some_and(|rest| rest.starts_with(&MAGIC)) {
+            break;
+        }
+
+        offsets.push(end);
+        start = end;
+    }
+    offsets
+}
+
+/// Describes bytes of a texture not covered by any region declared in its
+/// header, both gaps between regions and trailing data after the last one.
+fn layout_discrepancies(tex: &[u8], header: &TexHeader) -> Vec<String> {
+    let mut regions = header.regions().collect::<Vec<_>>();
+    regions.sort_by_key(|(_, region)| region.start);
+
+    let mut discrepancies = Vec::new();
+    let mut end = 0;
+    for (name, region) in regions {
+        if region.start > end {
+            discrepancies.push(format!(
+                "{} unused bytes at offset {end} before the {name}",
+                region.start - end
+            ));
+        }
+        end = end.max(region.end);
+    }
+    match tex.len().cmp(&end) {
+        Ordering::Greater => discrepancies.push(format!(
+            "{} bytes of trailing data at offset {end}",
+            tex.len() - end
+        )),
+        Ordering::Less => discrepancies.push(format!(
+            "declared regions extend {} bytes past the end of the file",
+            end - tex.len()
+        )),
+        Ordering::Equal => {}
+    }
+    discrepancies
+}
+
+/// Creates the file at `out_path`, and its missing parent directories if `create_dirs` is set.
+///
+/// Under `--strict` nothing is created if the current texture caused any warnings.
+fn create_output(out_path: &Path, create_dirs: bool) -> Option<std::fs::File> {
+    if STRICT.load(atomic::Ordering::Relaxed) && WARNINGS.get() > 0 {
+        message!(
+            "Not writing {} because of the errors above",
+            out_path.display()
+        );
+        return None;
+    }
+
+    if create_dirs {
+        if let Some(parent) = out_path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            if let Err(error) = std::fs::create_dir_all(parent) {
+                message!("Failed to create {}: {error}", parent.display());
+                return None;
+            }
+        }
+    }
+
+    match std::fs::File::create(out_path) {
+        Ok(file) => Some(file),
+        Err(error) => {
+            message!("Failed to create {}: {error}", out_path.display());
+            None
+        }
+    }
+}
+
+/// Writes the opaque bitmap of a texture as a grayscale PNG, with opaque texels
+/// white and all others black.
+fn emit_bitmap(tex: &[u8], header: &TexHeader, out_path: &Path, create_dirs: bool) -> ExitCode {
+    let (width, height) = (header.width as u32, header.height as u32);
+    let texels = width as usize * height as usize;
+    if header.opaque_bitmap == 0 {
+        message!("Texture has no opaque bitmap");
+        return Failure::Malformed.into();
+    }
+
+    let bitmap = match header.bitmap_range(tex.len()) {
+        Ok(range) => &tex[range],
+        Err(error) => {
+            message!("Invalid texture: {error}");
+            return Failure::Malformed.into();
+        }
+    };
+    if bitmap.len() < texels.div_ceil(8) {
+        message!("Opaque bitmap is too short for a {width}x{height} texture");
+        return Failure::Malformed.into();
+    }
+
+    let mask = (0..texels)
+        .map(|i| ((bitmap[i / 8] >> (i % 8)) & 1) * 0xFF)
+        .collect::<Vec<u8>>();
+
+    let Some(file) = create_output(out_path, create_dirs) else {
+        return Failure::Io.into();
+    };
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let result = encoder.write_header().and_then(|mut writer| {
+        writer
+            .write_image_data(&mask)
+            .and_then(|()| writer.finish())
+    });
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => encoding_failed(out_path, error),
+    }
+}
+
+/// Converts the pixel data of a `width` by `height` image in `format` to a PNG
+/// compatible buffer and its color type.
+///
+/// Formats which store RGBA with one byte per channel are converted in place and
+/// formats already compatible with PNG are returned as is, all others are decoded
+/// into `out` whose allocation is reused.
+fn decode_pixels<'a>(
+    format: TexFormat,
+    width: usize,
+    height: usize,
+    pixels: &'a mut [u8],
+    options: &DecodeOptions,
+    out: &'a mut Vec<u8>,
+) -> Option<(&'a [u8], png::ColorType)> {
+    match format {
+        TexFormat::A8 if !options.expand_rgba && matches!(options.a8_as, AlphaExpansion::Gray) => {
+            debug!("Writing {} bytes of alpha as gray", pixels.len());
+            Some((pixels, png::ColorType::Grayscale))
+        }
+        TexFormat::L8 if !options.expand_rgba => {
+            debug!("Writing {} bytes of luminance as is", pixels.len());
+            Some((pixels, png::ColorType::Grayscale))
+        }
+        TexFormat::La88 if !options.expand_rgba => {
+            debug!(
+                "Writing {} bytes of luminance and alpha as is",
+                pixels.len()
+            );
+            Some((pixels, png::ColorType::GrayscaleAlpha))
+        }
+        TexFormat::Bgr888 if !options.expand_rgba => {
+            debug!("Swapping red and blue of {} bytes in place", pixels.len());
+            for pixel in pixels.chunks_exact_mut(3) {
+                pixel.swap(0, 2);
+            }
+            Some((pixels, png::ColorType::Rgb))
+        }
+        TexFormat::Bgra8888 => {
+            let threads = decode_threads(format, width, height);
+            debug!(
+                "Swapping red and blue of {} bytes in place on {threads} thread(s)",
+                pixels.len()
+            );
+            if threads == 1 {
+                swizzle::bgra_to_rgba(pixels);
+            } else {
+                let band_size = height.div_ceil(threads) * width * 4;
+                std::thread::scope(|scope| {
+                    for band in pixels.chunks_mut(band_size) {
+                        scope.spawn(|| swizzle::bgra_to_rgba(band));
+                    }
+                });
+            }
+            Some((pixels, png::ColorType::Rgba))
+        }
+        _ if decode_threads(format, width, height) > 1 => {
+            let threads = decode_threads(format, width, height);
+            debug!("Decoding {} bytes on {threads} threads", pixels.len());
+            let color_type = decode_parallel(format, width, height, pixels, options, threads, out)?;
+            debug!("Decoded into a buffer of {} bytes", out.len());
+            Some((out, color_type))
+        }
+        _ if ROW_PROGRESS.load(atomic::Ordering::Relaxed) => {
+            debug!("Decoding {} bytes in bands of rows", pixels.len());
+            let color_type = decode_rows_into(format, width, height, pixels, options, out)?;
+            debug!("Decoded into a buffer of {} bytes", out.len());
+            Some((out, color_type))
+        }
+        _ => {
+            debug!("Decoding {} bytes", pixels.len());
+            let color_type = decode_into(format, width, height, pixels, options, out)?;
+            debug!("Decoded into a buffer of {} bytes", out.len());
+            Some((out, color_type))
+        }
+    }
+}
+
+/// Number of threads to decode a `width` by `height` texture in `format` on, one
+/// unless it's large enough for more to pay off.
+fn decode_threads(format: TexFormat, width: usize, height: usize) -> usize {
+    // PVRTC blocks blend into their neighbours, so bands can't be decoded on their own.
+    if format.is_pvrtc() || width * height < PARALLEL_DECODE_PIXELS {
+        return 1;
+    }
+    // Every thread gets at least a row of blocks.
+    DECODE_THREADS
+        .load(atomic::Ordering::Relaxed)
+        .min(height.div_ceil(4))
+        .max(1)
+}
+
+/// Like [`decode_into`], but decodes bands of rows on `threads` threads at once.
+///
+/// Bands start at multiples of the block height, so every block is decoded exactly
+/// like when decoding the whole texture. Each thread decodes straight into its
+/// part of `out`.
+fn decode_parallel(
+    format: TexFormat,
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+    options: &DecodeOptions,
+    threads: usize,
+    out: &mut Vec<u8>,
+) -> Option<png::ColorType> {
+    if !format.is_decodable() || format.is_pvrtc() {
+        return decode_into(format, width, height, pixels, options, out);
+    }
+
+    let (color_type, pixel_size) = decoded_layout(format, options);
+    out.clear();
+    out.resize(width * height * pixel_size, 0);
+    // A multiple of the block height of the ETC2 formats.
+    let band_height = height.div_ceil(threads).next_multiple_of(4);
+    let decoded_rows = AtomicUsize::new(0);
+    std::thread::scope(|scope| {
+        let handles = (0..height)
+            .step_by(band_height)
+            .zip(out.chunks_mut(band_height * width * pixel_size))
+            .map(|(start, band)| {
+                let rows = band_height.min(height - start);
+                let offset = format.expected_pixel_size(width, start);
+                let pixels = &pixels[offset..offset + format.expected_pixel_size(width, rows)];
+                let decoded_rows = &decoded_rows;
+                scope.spawn(move || {
+                    decode_bands(format, width, rows, pixels, options, band, |rows| {
+                        decoded_rows.fetch_add(rows, atomic::Ordering::Relaxed);
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if ROW_PROGRESS.load(atomic::Ordering::Relaxed) {
+            let mut progress = progress::RowProgress::new("decoding", height);
+            while !handles.iter().all(|handle| handle.is_finished()) {
+                progress.update(decoded_rows.load(atomic::Ordering::Relaxed));
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            progress.finish();
+        }
+    });
+    Some(color_type)
+}
+
+/// Number of rows decoded at a time when decoding a band at a time, a multiple of
+/// the block height of the ETC2 formats.
+const BAND_HEIGHT: usize = 64;
+
+/// Like [`decode_into`], but decodes bands of rows one after another to show
+/// how far along decoding is.
+fn decode_rows_into(
+    format: TexFormat,
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+    options: &DecodeOptions,
+    out: &mut Vec<u8>,
+) -> Option<png::ColorType> {
+    if !format.is_decodable() {
+        return decode_into(format, width, height, pixels, options, out);
+    }
+
+    let mut progress = progress::RowProgress::new("decoding", height);
+    // PVRTC blocks are stored in Morton order and blend into their neighbours,
+    // so they can't be decoded a band at a time and report every row instead.
+    #[cfg(feature = "pvrtc")]
+    if format.is_pvrtc() {
+        let color_type = decode_pvrtc(format, width, height, pixels, out, |rows| {
+            progress.update(rows)
+        });
+        progress.finish();
+        return color_type;
+    }
+
+    let (color_type, pixel_size) = decoded_layout(format, options);
+    out.clear();
+    out.resize(width * height * pixel_size, 0);
+    let mut decoded_rows = 0;
+    decode_bands(format, width, height, pixels, options, out, |rows| {
+        decoded_rows += rows;
+        progress.update(decoded_rows);
+    });
+    progress.finish();
+
+    Some(color_type)
+}
+
+/// Decodes like [`decode_to_slice`] a band of [`BAND_HEIGHT`] rows at a time,
+/// calling `rows_done` with the number of rows in each band once it's decoded.
+fn decode_bands(
+    format: TexFormat,
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+    options: &DecodeOptions,
+    out: &mut [u8],
+    mut rows_done: impl FnMut(usize),
+) {
+    let pixel_size = decoded_layout(format, options).1;
+    for (start, band) in (0..height)
+        .step_by(BAND_HEIGHT)
+        .zip(out.chunks_mut(BAND_HEIGHT * width * pixel_size))
+    {
+        let rows = BAND_HEIGHT.min(height - start);
+        let offset = format.expected_pixel_size(width, start);
+        let size = format.expected_pixel_size(width, rows);
+        decode_to_slice(
+            format,
+            width,
+            rows,
+            &pixels[offset..offset + size],
+            options,
+            band,
+        );
+        rows_done(rows);
+    }
+}
+
+/// Expands pixels of `N` bytes each at the start of `buffer` to RGBA in place,
+/// where `buffer` holds four bytes for each pixel.
+///
+/// The buffer is filled from the last pixel backwards: pixel `i` is written to
+/// bytes `4 * i..` which never reach the packed pixels before it, so each pixel
+/// is read before anything overwrites it.
+fn expand_in_place<const N: usize>(buffer: &mut [u8], expand: impl Fn([u8; N]) -> [u8; 4]) {
+    for i in (0..buffer.len() / 4).rev() {
+        let pixel = buffer[i * N..][..N].try_into().unwrap();
+        buffer[i * 4..][..4].copy_from_slice(&expand(pixel));
+    }
+}
+
+/// Whether [`decode_in_place`] expands pixels in `format` to RGBA, rather than
+/// only rearranging them or not decoding them at all.
+fn expands_in_place(format: TexFormat, options: &DecodeOptions) -> bool {
+    match format {
+        TexFormat::A8 | TexFormat::L8 | TexFormat::La88 | TexFormat::Bgr888 => options.expand_rgba,
+        TexFormat::Bgra5551 | TexFormat::Bgra4444 | TexFormat::Rgba4444 => true,
+        _ => false,
+    }
+}
+
+/// Decodes pixels in `format` held at the start of `buffer` to RGBA in place,
+/// so decoding needs no memory besides the grown buffer.
+///
+/// Returns whether `format` could be decoded like this, which is the case for
+/// formats with at most four bytes per pixel that are expanded to RGBA.
+fn decode_in_place(format: TexFormat, buffer: &mut Vec<u8>, options: &DecodeOptions) -> bool {
+    if format != TexFormat::Bgra8888 && !expands_in_place(format, options) {
+        return false;
+    }
+    let pixel_size = format.bytes_per_pixel().unwrap();
+    buffer.resize(buffer.len() / pixel_size * 4, 0);
+    rgba_in_place(format, buffer, options);
+    true
+}
+
+/// Decodes the pixels in `format` at the start of `buffer` to RGBA in place, where
+/// `buffer` holds four bytes for each pixel and `format` is one
+/// [`decode_in_place`] decodes.
+fn rgba_in_place(format: TexFormat, buffer: &mut [u8], options: &DecodeOptions) {
+    let endian = options.pixel_endian.unwrap_or_default();
+    match format {
+        TexFormat::A8 => expand_in_place(buffer, |[value]| match options.a8_as {
+            AlphaExpansion::White => [0xFF, 0xFF, 0xFF, value],
+            AlphaExpansion::Gray => [value, value, value, 0xFF],
+        }),
+        TexFormat::L8 => expand_in_place(buffer, |[luminance]| {
+            [luminance, luminance, luminance, 0xFF]
+        }),
+        TexFormat::La88 => expand_in_place(buffer, |[luminance, alpha]| {
+            [luminance, luminance, luminance, alpha]
+        }),
+        TexFormat::Bgr888 => expand_in_place(buffer, |[blue, green, red]| [red, green, blue, 0xFF]),
+        TexFormat::Bgra8888 => swizzle::bgra_to_rgba(buffer),
+        TexFormat::Bgra5551 => expand_in_place(buffer, |pixel| {
+            let value = endian.read_u16(pixel) as u32;
+            let expand = |bits: u32| ((bits & 0x1F) * 0xFF / 0x1F) as u8;
+            [
+                expand(value >> 10),
+                expand(value >> 5),
+                expand(value),
+                (value >> 15) as u8 * 0xFF,
+            ]
+        }),
+        TexFormat::Bgra4444 => expand_in_place(buffer, |pixel| {
+            let value = endian.read_u16(pixel);
+            [
+                expand_nibble(value >> 8),
+                expand_nibble(value >> 4),
+                expand_nibble(value),
+                expand_nibble(value >> 12),
+            ]
+        }),
+        TexFormat::Rgba4444 => expand_in_place(buffer, |pixel| {
+            let value = endian.read_u16(pixel);
+            [
+                expand_nibble(value >> 12),
+                expand_nibble(value >> 8),
+                expand_nibble(value >> 4),
+                expand_nibble(value),
+            ]
+        }),
+        _ => unreachable!("{format:?} can't be decoded in place"),
+    }
+}
+
+/// Color type and bytes per pixel that pixels in `format` are decoded to.
+fn decoded_layout(format: TexFormat, options: &DecodeOptions) -> (png::ColorType, usize) {
+    match format {
+        TexFormat::A8 if !options.expand_rgba => match options.a8_as {
+            AlphaExpansion::White => (png::ColorType::GrayscaleAlpha, 2),
+            AlphaExpansion::Gray => (png::ColorType::Grayscale, 1),
+        },
+        TexFormat::L8 if !options.expand_rgba => (png::ColorType::Grayscale, 1),
+        TexFormat::La88 if !options.expand_rgba => (png::ColorType::GrayscaleAlpha, 2),
+        TexFormat::Bgr888 if !options.expand_rgba => (png::ColorType::Rgb, 3),
+        TexFormat::Rgba16 => (png::ColorType::Rgba, 8),
+        _ => (png::ColorType::Rgba, 4),
+    }
+}
+
+/// Like [`decode_pixels`], but always decodes into `out` after clearing it.
+fn decode_into(
+    format: TexFormat,
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+    options: &DecodeOptions,
+    out: &mut Vec<u8>,
+) -> Option<png::ColorType> {
+    out.clear();
+    if !format.is_decodable() {
+        message!("Conversion from {format:?} is not enabled in this build");
+        return None;
+    }
+
+    #[cfg(feature = "pvrtc")]
+    if format.is_pvrtc() {
+        return decode_pvrtc(format, width, height, pixels, out, |_| {});
+    }
+
+    let (color_type, pixel_size) = decoded_layout(format, options);
+    out.resize(width * height * pixel_size, 0);
+    decode_to_slice(format, width, height, pixels, options, out);
+    Some(color_type)
+}
+
+/// Decodes a texture in one of the PVRTC formats into `out` after clearing it,
+/// calling `rows_done` like [`pvrtc::decode`].
+#[cfg(feature = "pvrtc")]
+fn decode_pvrtc(
+    format: TexFormat,
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+    out: &mut Vec<u8>,
+    rows_done: impl FnMut(usize),
+) -> Option<png::ColorType> {
+    out.clear();
+    let two_bit = matches!(format, TexFormat::Pvrtc2Rgba | TexFormat::Pvrtc2Rgb);
+    if !pvrtc::decode(pixels, width, height, two_bit, out, rows_done) {
+        message!(
+            "PVRTC textures have to be a power of two blocks wide and high, got {width}x{height}"
+        );
+        return None;
+    }
+    Some(png::ColorType::Rgba)
+}
+
+/// Decodes the pixels of a `width` by `height` texture in `format` into `out`,
+/// which holds exactly as many bytes as [`decoded_layout`] asks for.
+///
+/// `format` has to be decodable in this build and not a PVRTC format, whose
+/// blocks can't be decoded without the whole texture.
+fn decode_to_slice(
+    format: TexFormat,
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+    options: &DecodeOptions,
+    out: &mut [u8],
+) {
+    debug_assert_eq!(
+        out.len(),
+        width * height * decoded_layout(format, options).1
+    );
+    let endian = options.pixel_endian.unwrap_or_default();
+    match format {
+        TexFormat::A8 if !options.expand_rgba => match options.a8_as {
+            AlphaExpansion::White => {
+                for (&alpha, pixel) in pixels.iter().zip(out.chunks_exact_mut(2)) {
+                    pixel.copy_from_slice(&[0xFF, alpha]);
+                }
+            }
+            AlphaExpansion::Gray => out.copy_from_slice(pixels),
+        },
+        TexFormat::L8 | TexFormat::La88 if !options.expand_rgba => out.copy_from_slice(pixels),
+        TexFormat::Bgr888 if !options.expand_rgba => {
+            for (bgr, rgb) in pixels.chunks_exact(3).zip(out.chunks_exact_mut(3)) {
+                rgb.copy_from_slice(&[bgr[2], bgr[1], bgr[0]]);
+            }
+        }
+        TexFormat::A8
+        | TexFormat::L8
+        | TexFormat::La88
+        | TexFormat::Bgr888
+        | TexFormat::Bgra8888
+        | TexFormat::Bgra5551
+        | TexFormat::Bgra4444
+        | TexFormat::Rgba4444 => {
+            out[..pixels.len()].copy_from_slice(pixels);
+            rgba_in_place(format, out, options);
+        }
+        #[cfg(feature = "etc")]
+        TexFormat::Etc2Rgb => etc::decode(pixels, width, height, false, out),
+        #[cfg(feature = "etc")]
+        TexFormat::Etc2Rgba => etc::decode(pixels, width, height, true, out),
+        TexFormat::Rgba16 => {
+            // PNG stores 16-bit samples big-endian.
+            for (sample, out) in pixels.chunks_exact(2).zip(out.chunks_exact_mut(2)) {
+                let value = endian.read_u16([sample[0], sample[1]]);
+                out.copy_from_slice(&value.to_be_bytes());
+            }
+        }
+        _ => unreachable!("{format:?} can't be decoded into a slice"),
+    }
+}
+
+/// Narrows big-endian 16-bit samples decoded from formats with 16-bit channels
+/// to 8 bits in place, keeping the high byte like PNG readers stripping 16-bit
+/// images do.
+fn narrow_to_8_bit(buffer: &mut Vec<u8>) {
+    for i in 0..buffer.len() / 2 {
+        buffer[i] = buffer[2 * i];
+    }
+    buffer.truncate(buffer.len() / 2);
+}
+
+/// Reports a mismatched magic, returns whether parsing should continue.
+fn check_magic(header: &TexHeader, ignore_magic: bool) -> bool {
+    if header.magic == MAGIC {
+        return true;
+    }
+
+    let found = header.magic.map(|byte| format!("{byte:02X}")).join(" ");
+    if ignore_magic {
+        warning!("Mismatched magic {found}, continuing anyway");
+    } else {
+        message!("File is not a tex file: mismatched magic, found {found}");
+    }
+    ignore_magic
+}
+
+/// How to handle textures with a format value that isn't known.
+#[derive(Debug, Clone, Copy)]
+enum UnknownFormat {
+    Reject,
+    /// Guess the format from the pixel data size.
+    Guess,
+    /// Decode the pixel data as if it was in the given format.
+    Assume(TexFormat),
+}
+
+/// Parses and validates the header of `tex` and resolves its pixel format.
+fn parse_texture(
+    tex: &[u8],
+    unknown_format: UnknownFormat,
+    ignore_magic: bool,
+) -> Result<(TexHeader, TexFormat), Failure> {
+    let Some(header) = TexHeader::parse(tex) else {
+        message!("File is not a tex file: too short");
+        return Err(Failure::Malformed);
+    };
+    if !check_magic(&header, ignore_magic) {
+        return Err(Failure::Malformed);
+    }
+    info!(
+        "Header: version {}, format 0x{:02X}, {}x{}, {} mipmaps, scale {}, opaque bitmap {}",
+        header.version,
+        header.format,
+        header.width,
+        header.height,
+        header.mipmaps,
+        header.scale,
+        header.opaque_bitmap
+    );
+    info!(
+        "Pixel data: {} bytes at offset {}, opaque bitmap: {} bytes at offset {}",
+        header.pixels_size, header.pixels_offset, header.bitmap_size, header.bitmap_offset
+    );
+
+    if header.version != 2 {
+        message!("Unsupported tex file version: {}", header.version);
+        return Err(Failure::Unsupported);
+    }
+    if header.width < 0 || header.height < 0 {
+        message!(
+            "Invalid texture: negative size {}x{}",
+            header.width,
+            header.height
+        );
+        return Err(Failure::Malformed);
+    }
+
+    let format = match TexFormat::from_value(header.format) {
+        Some(format) => format,
+        None if matches!(unknown_format, UnknownFormat::Guess) => {
+            let Some(format) = TexFormat::guess(
+                header.width as usize,
+                header.height as usize,
+                header.pixels_size as usize,
+            ) else {
+                message!(
+                    "Unsupported texture format: 0x{:02X}, unable to guess a layout",
+                    header.format
+                );
+                return Err(Failure::Unsupported);
+            };
+            warning!(
+                "Unknown texture format 0x{:02X}, guessing {format:?} from the pixel data size",
+                header.format
+            );
+            format
+        }
+        None => match unknown_format {
+            UnknownFormat::Assume(format) => {
+                warning!(
+                    "Unknown texture format 0x{:02X}, decoding it as {format:?}",
+                    header.format
+                );
+                format
+            }
+            _ => {
+                message!("Unsupported texture format: 0x{:02X}", header.format);
+                return Err(Failure::Unsupported);
+            }
+        },
+    };
+
+    // Every declared mipmap level has to fit, so that no code path reads past the
+    // pixel data when looking for one.
+    let level_count = (header.mipmaps as usize).max(1);
+    let levels = mip_levels(
+        format,
+        header.width as usize,
+        header.height as usize,
+        level_count,
+    );
+    let last = levels.last().unwrap();
+    let needed = last.offset + last.size;
+    if needed > header.pixels_size.max(0) as usize {
+        message!(
+            "Invalid texture: {level_count} mipmap levels need {needed} bytes of pixel data but only {} are declared",
+            header.pixels_size
+        );
+        return Err(Failure::Malformed);
+    }
+    info!("Format: {format:?}");
+    for (i, level) in levels.iter().enumerate() {
+        debug!(
+            "Mipmap level {i}: {}x{}, {} bytes at offset {}",
+            level.width, level.height, level.size, level.offset
+        );
+    }
+
+    Ok((header, format))
+}
+
+/// A cell of the grid requested with `--slice` or `--cell`.
+struct SliceCell {
+    row: u32,
+    column: u32,
+    /// Part of the cell within `--cell-padding`.
+    region: transform::Rect,
+}
+
+/// Cells of the image requested with `--slice` or `--cell`, in row-major order.
+///
+/// Fails if the image is smaller than a single cell or the padding covers a cell.
+fn slice_cells(
+    tex2png: &Tex2Png,
+    width: u32,
+    height: u32,
+) -> Result<Option<Vec<SliceCell>>, String> {
+    let (grid, cell_width, cell_height) = match (tex2png.slice, tex2png.cell) {
+        (Some(grid), _) => (grid, width / grid.columns, height / grid.rows),
+        (None, Some(cell)) => (
+            transform::Grid {
+                columns: width / cell.width,
+                rows: height / cell.height,
+            },
+            cell.width,
+            cell.height,
+        ),
+        (None, None) => return Ok(None),
+    };
+
+    if cell_width == 0 || cell_height == 0 || grid.columns == 0 || grid.rows == 0 {
+        return Err(format!(
+            "Image of {width}x{height} is too small to be sliced"
+        ));
+    }
+    if grid.columns * cell_width != width || grid.rows * cell_height != height {
+        warning!(
+            "{width}x{height} image is not divisible into {cell_width}x{cell_height} cells, dropping the remaining pixels"
+        );
+    }
+    let padding = tex2png.cell_padding;
+    if padding.saturating_mul(2) >= cell_width.min(cell_height) {
+        return Err(format!(
+            "Cell padding of {padding} leaves nothing of {cell_width}x{cell_height} cells"
+        ));
+    }
+
+    let cells = grid.cells_of_size(cell_width, cell_height);
+    Ok(Some(
+        cells
+            .into_iter()
+            .map(|cell| SliceCell {
+                row: cell.y / cell.height,
+                column: cell.x / cell.width,
+                region: transform::Rect {
+                    x: cell.x + padding,
+                    y: cell.y + padding,
+                    width: cell.width - 2 * padding,
+                    height: cell.height - 2 * padding,
+                },
+            })
+            .collect(),
+    ))
+}
+
+/// Converts a single texture, `index` is its position within the file if it
+/// contains several concatenated textures.
+fn convert_texture(
+    file: &mut Vec<u8>,
+    texture: std::ops::Range<usize>,
+    tex_path: &Path,
+    out_path: &Path,
+    index: Option<usize>,
+    tex2png: &Tex2Png,
+    decoded: &mut Vec<u8>,
+) -> ExitCode {
+    let whole_file = texture == (0..file.len());
+    let tex = &mut file[texture.clone()];
+    WARNINGS.set(0);
+    let unknown_format = match tex2png.assume_format {
+        Some(format) => UnknownFormat::Assume(format),
+        None if tex2png.guess_format => UnknownFormat::Guess,
+        None => UnknownFormat::Reject,
+    };
+    let (header, format) = match parse_texture(tex, unknown_format, tex2png.ignore_magic) {
+        Ok(parsed) => parsed,
+        Err(failure) => return failure.into(),
+    };
+    if header.width as u32 > tex2png.max_dimension || header.height as u32 > tex2png.max_dimension {
+        message!(
+            "Texture size {}x{} exceeds the maximum dimension of {}, raise --max-dimension to convert it",
+            header.width,
+            header.height,
+            tex2png.max_dimension
+        );
+        return Failure::Unsupported.into();
+    }
+    if tex2png.only_format.is_some_and(|only| only != format) {
+        message!("Skipping {} in {format:?} format", tex_path.display());
+        return ExitCode::SUCCESS;
+    }
+
+    for discrepancy in layout_discrepancies(tex, &header) {
+        warning!("Unexpected texture layout: {discrepancy}");
+    }
+
+    let mut out_path = match &tex2png.output_template {
+        Some(template) => out_path.with_file_name(template.expand(&template::TemplateFields {
+            stem: &tex_path.file_stem().unwrap_or_default().to_string_lossy(),
+            ext: &tex_path.extension().unwrap_or_default().to_string_lossy(),
+            width: header.width as u32,
+            height: header.height as u32,
+            format,
+            index: index.unwrap_or(0),
+        })),
+        None => out_path.to_path_buf(),
+    };
+    let index_in_name = tex2png
+        .output_template
+        .as_ref()
+        .is_some_and(|template| template.uses_index());
+    let with_index = |path: &mut PathBuf| {
+        if let Some(index) = index.filter(|_| !index_in_name) {
+            let extension = path.extension().unwrap_or_default().to_string_lossy();
+            path.set_extension(format!("{index}.{extension}"));
+        }
+    };
+    with_index(&mut out_path);
+    let out_path = out_path.as_path();
+
+    if tex2png.dry_run {
+        let extra_paths = tex2png.outputs.iter().map(|path| {
+            let mut path = path.clone();
+            with_index(&mut path);
+            path
+        });
+        let input = match index {
+            Some(index) => format!("{} (texture {index})", tex_path.display()),
+            None => tex_path.display().to_string(),
+        };
+        return print_plan(&input, &header, out_path, extra_paths.collect(), tex2png);
+    }
+
+    if let Some(bitmap_path) = &tex2png.emit_bitmap {
+        let outcome = emit_bitmap(tex, &header, bitmap_path, tex2png.create_dirs());
+        if outcome != ExitCode::SUCCESS {
+            return outcome;
+        }
+    }
+    if tex2png.mipmaps {
+        return convert_mipmaps(tex, &header, format, out_path, tex2png);
+    }
+
+    let level_count = (header.mipmaps as usize).max(1);
+    if tex2png.mip >= level_count {
+        message!(
+            "Mipmap level {} requested but the texture only has {level_count} levels",
+            tex2png.mip
+        );
+        return Failure::Malformed.into();
+    }
+    let levels = mip_levels(
+        format,
+        header.width as usize,
+        header.height as usize,
+        level_count,
+    );
+    let level = levels[tex2png.mip];
+
+    let pixels_range = match header.pixels_range(tex.len()) {
+        Ok(range) => range,
+        Err(error) => {
+            message!("Invalid texture: {error}");
+            return Failure::Malformed.into();
+        }
+    };
+    let Some(pixels) = take_pixels(&mut tex[pixels_range.clone()], &levels, tex2png.mip) else {
+        return Failure::Malformed.into();
+    };
+    let (width, height) = (level.width, level.height);
+    warn_about_byte_order(format, tex2png);
+    if format.is_16_bit() {
+        for (name, used) in [
+            ("--trim", tex2png.trim),
+            ("--resize", tex2png.resize.is_some()),
+            ("--scale-factor", tex2png.scale_factor.is_some()),
+        ] {
+            if used {
+                message!("{name} is not supported for 16-bit textures");
+                return Failure::Unsupported.into();
+            }
+        }
+    }
+
+    let start = Instant::now();
+    let options = &tex2png.decode_options;
+    let decoded_pixels = if whole_file
+        && expands_in_place(format, options)
+        && decode_threads(format, width, height) == 1
+        && !ROW_PROGRESS.load(atomic::Ordering::Relaxed)
+    {
+        // The level is expanded where the file was read to, so the file and the
+        // decoded pixels don't have to be held at once.
+        debug!("Expanding {} bytes in place", pixels.len());
+        let level_start = texture.start + pixels_range.start + level.offset;
+        file.truncate(level_start + level.size);
+        file.drain(..level_start);
+        decode_in_place(format, file, options);
+        std::mem::swap(file, decoded);
+        Some((&decoded[..], png::ColorType::Rgba))
+    } else {
+        decode_pixels(format, width, height, pixels, options, decoded)
+    };
+    let Some((buffer, color_type)) = decoded_pixels else {
+        return Failure::Unsupported.into();
+    };
+    debug!("Decoded {width}x{height} pixels in {:.1?}", start.elapsed());
+    if tex2png.verify {
+        return verified();
+    }
+
+    let depth = sample_depth(format);
+    let pixel_size = color_type.samples() * if format.is_16_bit() { 2 } else { 1 };
+    let adjusted = adjust_colors(buffer, width, color_type, depth, tex2png);
+    let buffer: &[u8] = &adjusted;
+
+    let (mut width, mut height) = (width as u32, height as u32);
+    if tex2png.cubemap {
+        let layout = tex2png.cube_layout;
+        let Some(size) = layout.face_size(width, height) else {
+            message!("A {width}x{height} texture isn't a cube map in the {layout:?} layout");
+            return Failure::Malformed.into();
+        };
+        let mut result = ExitCode::SUCCESS;
+        for (face, face_path) in cube_face_paths(out_path, tex2png) {
+            let frames = [Frame {
+                pixels: Cow::Owned(transform::crop(
+                    buffer,
+                    width,
+                    pixel_size,
+                    layout.face_rect(face, size),
+                )),
+                width: size,
+                height: size,
+            }];
+            let outcome = write_output(&face_path, color_type, depth, &frames, &header, tex2png);
+            keep_first_failure(&mut result, outcome);
+        }
+        return result;
+    }
+
+    let cropped;
+    let buffer =
+        match tex2png.crop {
+            Some(rect) => {
+                if !rect.fits_within(width, height) {
+                    message!(
+                    "Crop region {}x{} at {},{} does not fit within the {width}x{height} texture",
+                    rect.width, rect.height, rect.x, rect.y
+                );
+                    return Failure::Malformed.into();
+                }
+
+                cropped = transform::crop(buffer, width, pixel_size, rect);
+                (width, height) = (rect.width, rect.height);
+                &cropped
+            }
+            None => buffer,
+        };
+
+    let trimmed;
+    let buffer = if tex2png.trim
+        && matches!(
+            color_type,
+            png::ColorType::Rgba | png::ColorType::GrayscaleAlpha
+        ) {
+        let channels = color_type.samples();
+        let rect = match transform::opaque_bounds(buffer, width, channels) {
+            Some(rect) => {
+                trimmed = transform::crop(buffer, width, channels, rect);
+                rect
+            }
+            // Keep a single transparent pixel since PNGs can't be empty.
+            None => {
+                trimmed = vec![0; channels];
+                transform::Rect {
+                    x: 0,
+                    y: 0,
+                    width: 1,
+                    height: 1,
+                }
+            }
+        };
+        output!(
+            "{} {} {} {} {}",
+            out_path.display(),
+            rect.x,
+            rect.y,
+            rect.width,
+            rect.height
+        );
+        (width, height) = (rect.width, rect.height);
+        &trimmed
+    } else {
+        buffer
+    };
+
+    let rotated;
+    let buffer = match tex2png.rotate {
+        Some(rotation) => {
+            (rotated, (width, height)) =
+                transform::rotate(buffer, (width, height), pixel_size, rotation);
+            &rotated
+        }
+        None => buffer,
+    };
+
+    let size = match (tex2png.resize, tex2png.scale_factor) {
+        (Some(target), _) => Some(target.resolve(width, height)),
+        (None, Some(factor)) => Some(resize::Size {
+            width: ((width as f64 * factor).round() as u32).max(1),
+            height: ((height as f64 * factor).round() as u32).max(1),
+        }),
+        (None, None) => None,
+    };
+    let resized;
+    let buffer = match size {
+        Some(size) => {
+            resized = resize::resize(
+                buffer,
+                (width, height),
+                color_type.samples(),
+                size,
+                tex2png.filter,
+            );
+            (width, height) = (size.width, size.height);
+            &resized
+        }
+        None => buffer,
+    };
+
+    if let Some(atlas_path) = &tex2png.atlas {
+        let sprites = match atlas::read(atlas_path) {
+            Ok(sprites) => sprites,
+            Err((failure, error)) => {
+                message!("Failed to read atlas {}: {error}", atlas_path.display());
+                return failure.into();
+            }
+        };
+        let sprite_dir = out_path.with_extension("");
+        let mut result = ExitCode::SUCCESS;
+        for sprite in sprites {
+            if sprite.name.is_empty() || !zip::is_safe_entry_name(&sprite.name) {
+                warning!("Skipping sprite with unsafe name {:?}", sprite.name);
+                continue;
+            }
+            let rect = sprite.rect;
+            if !rect.fits_within(width, height) {
+                message!(
+                    "Sprite {} of {}x{} at {},{} does not fit within the {width}x{height} texture",
+                    sprite.name,
+                    rect.width,
+                    rect.height,
+                    rect.x,
+                    rect.y
+                );
+                keep_first_failure(&mut result, Failure::Malformed.into());
+                continue;
+            }
+
+            let mut sprite_path = sprite_dir.join(&sprite.name).into_os_string();
+            sprite_path.push(format!(".{}", tex2png.output_extension()));
+            let mut pixels = transform::crop(buffer, width, pixel_size, rect);
+            let (mut sprite_width, mut sprite_height) = (rect.width, rect.height);
+            if sprite.rotated {
+                // Packed turned a quarter clockwise, three more quarters turn it upright.
+                (pixels, (sprite_width, sprite_height)) = transform::rotate(
+                    &pixels,
+                    (rect.width, rect.height),
+                    pixel_size,
+                    transform::Rotation::ThreeQuarters,
+                );
+            }
+            if let Some(trim) = sprite.trim {
+                pixels = transform::pad(&pixels, sprite_width, pixel_size, trim);
+                (sprite_width, sprite_height) = (trim.width, trim.height);
+            }
+            let frames = [Frame {
+                pixels: Cow::Owned(pixels),
+                width: sprite_width,
+                height: sprite_height,
+            }];
+            let outcome = write_output(
+                Path::new(&sprite_path),
+                color_type,
+                depth,
+                &frames,
+                &header,
+                tex2png,
+            );
+            keep_first_failure(&mut result, outcome);
+        }
+        return result;
+    }
+
+    let cells = match slice_cells(tex2png, width, height) {
+        Ok(cells) => cells,
+        Err(error) => {
+            message!("{error}");
+            return Failure::Malformed.into();
+        }
+    };
+    if let Some(cells) = cells {
+        let stem = out_path.file_stem().unwrap_or_default().to_string_lossy();
+        let extension = out_path.extension().unwrap_or_default().to_string_lossy();
+        let mut result = ExitCode::SUCCESS;
+        for SliceCell {
+            row,
+            column,
+            region: cell,
+        } in cells
+        {
+            let cell_path = out_path.with_file_name(format!("{stem}_{row}_{column}.{extension}"));
+            let pixels = transform::crop(buffer, width, pixel_size, cell);
+            let frames = [Frame {
+                pixels: Cow::Owned(pixels),
+                width: cell.width,
+                height: cell.height,
+            }];
+            let outcome = write_output(&cell_path, color_type, depth, &frames, &header, tex2png);
+            keep_first_failure(&mut result, outcome);
+        }
+        return result;
+    }
+
+    let frames = match tex2png.frames {
+        Some(grid) => {
+            if grid.columns > width || grid.rows > height {
+                message!(
+                    "Cannot slice a {width}x{height} image into {}x{} frames",
+                    grid.columns,
+                    grid.rows
+                );
+                return Failure::Malformed.into();
+            }
+            if width % grid.columns != 0 || height % grid.rows != 0 {
+                warning!(
+                    "{width}x{height} image is not divisible into {}x{} frames, dropping the remaining pixels",
+                    grid.columns, grid.rows
+                );
+            }
+
+            grid.cells(width, height)
+                .into_iter()
+                .map(|cell| Frame {
+                    pixels: Cow::Owned(transform::crop(buffer, width, pixel_size, cell)),
+                    width: cell.width,
+                    height: cell.height,
+                })
+                .collect()
+        }
+        None => vec![Frame {
+            pixels: Cow::Borrowed(buffer),
+            width,
+            height,
+        }],
+    };
+
+    if tex2png.alpha_preview {
+        return write_alpha_preview(out_path, color_type, depth, &frames, &header, tex2png);
+    }
+    let mut result = write_output(out_path, color_type, depth, &frames, &header, tex2png);
+    for extra_path in &tex2png.outputs {
+        let mut extra_path = extra_path.clone();
+        with_index(&mut extra_path);
+        let outcome = write_output(&extra_path, color_type, depth, &frames, &header, tex2png);
+        keep_first_failure(&mut result, outcome);
+    }
+    result
+}
+
+/// Prints every file converting the texture read from `input` would write, under --dry-run.
+fn print_plan(
+    input: &str,
+    header: &TexHeader,
+    out_path: &Path,
+    extra_paths: Vec<PathBuf>,
+    tex2png: &Tex2Png,
+) -> ExitCode {
+    let mut outputs = Vec::new();
+    outputs.extend(tex2png.emit_bitmap.clone());
+    let extension = out_path.extension().unwrap_or_default().to_string_lossy();
+    if tex2png.mipmaps && !tex2png.apng {
+        outputs.extend(
+            (0..(header.mipmaps as usize).max(1))
+                .map(|i| out_path.with_extension(format!("mip{i}.{extension}"))),
+        );
+    } else if let Some(atlas_path) = &tex2png.atlas {
+        let sprites = match atlas::read(atlas_path) {
+            Ok(sprites) => sprites,
+            Err((failure, error)) => {
+                message!("Failed to read atlas {}: {error}", atlas_path.display());
+                return failure.into();
+            }
+        };
+        let sprite_dir = out_path.with_extension("");
+        for sprite in sprites {
+            if sprite.name.is_empty() || !zip::is_safe_entry_name(&sprite.name) {
+                warning!("Skipping sprite with unsafe name {:?}", sprite.name);
+                continue;
+            }
+            let mut sprite_path = sprite_dir.join(&sprite.name).into_os_string();
+            sprite_path.push(format!(".{}", tex2png.output_extension()));
+            outputs.push(sprite_path.into());
+        }
+    } else if tex2png.cubemap {
+        outputs.extend(cube_face_paths(out_path, tex2png).map(|(_, path)| path));
+    } else if tex2png.slice.is_some() || tex2png.cell.is_some() {
+        // Which cells there are depends on the size of the decoded image.
+        let stem = out_path.file_stem().unwrap_or_default().to_string_lossy();
+        outputs.push(out_path.with_file_name(format!("{stem}_<row>_<column>.{extension}")));
+    } else {
+        outputs.push(out_path.to_path_buf());
+        if tex2png.alpha_preview {
+            outputs.push(out_path.with_extension(format!("magenta.{extension}")));
+        }
+        outputs.extend(extra_paths);
+    }
+
+    for output in outputs {
+        let overwrites = if output.exists() {
+            " (overwrites an existing file)"
+        } else {
+            ""
+        };
+        output!("{input} -> {}{overwrites}", output.display());
+    }
+    ExitCode::SUCCESS
+}
+
+/// The faces written by --cubemap and their paths: all of them next to `out_path`,
+/// or only the one chosen with --face to `out_path` itself.
+fn cube_face_paths<'a>(
+    out_path: &'a Path,
+    tex2png: &Tex2Png,
+) -> impl Iterator<Item = (cubemap::Face, PathBuf)> + 'a {
+    let face = tex2png.face;
+    let faces = match face {
+        Some(face) => vec![face],
+        None => cubemap::Face::ALL.to_vec(),
+    };
+    let stem = out_path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = out_path.extension().unwrap_or_default().to_string_lossy();
+    faces.into_iter().map(move |chosen| {
+        let path = match face {
+            Some(_) => out_path.to_path_buf(),
+            None => out_path.with_file_name(format!("{stem}_{}.{extension}", chosen.suffix())),
+        };
+        (chosen, path)
+    })
+}
+
+/// Result of a successfully decoded texture under --verify, which fails on warnings with --strict.
+fn verified() -> ExitCode {
+    if STRICT.load(atomic::Ordering::Relaxed) && WARNINGS.get() > 0 {
+        Failure::Malformed.into()
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Applies --colorkey, --unpremultiply or --auto-unpremultiply, --alpha-threshold,
+/// --bleed, --gamma and --swizzle to decoded pixels of an image that is `width` pixels wide.
+///
+/// Only --swizzle supports 16-bit samples, the other options are ignored for them.
+fn adjust_colors<'a>(
+    buffer: &'a [u8],
+    width: usize,
+    color_type: png::ColorType,
+    depth: png::BitDepth,
+    tex2png: &Tex2Png,
+) -> Cow<'a, [u8]> {
+    let mut buffer = Cow::Borrowed(buffer);
+    if depth == png::BitDepth::Sixteen {
+        for (name, used) in [
+            ("--colorkey", tex2png.colorkey.is_some()),
+            ("--unpremultiply", tex2png.unpremultiply),
+            ("--auto-unpremultiply", tex2png.auto_unpremultiply),
+            ("--alpha-threshold", tex2png.alpha_threshold.is_some()),
+            ("--bleed", tex2png.bleed),
+            ("--gamma", tex2png.gamma.is_some()),
+        ] {
+            if used {
+                warning!("Ignoring {name} for 16-bit output");
+            }
+        }
+        match tex2png.swizzle {
+            Some(swizzle) if color_type == png::ColorType::Rgba => {
+                buffer = Cow::Owned(swizzle.apply_16(&buffer));
+            }
+            Some(_) => warning!("Ignoring --swizzle for {color_type:?} output"),
+            None => {}
+        }
+        return buffer;
+    }
+
+    if let Some(key) = tex2png.colorkey {
+        if color_type == png::ColorType::Rgba {
+            transform::apply_color_key(buffer.to_mut(), key, tex2png.colorkey_tolerance);
+        } else {
+            warning!("Ignoring --colorkey for {color_type:?} output");
+        }
+    }
+    if tex2png.unpremultiply {
+        if color_type == png::ColorType::Rgba {
+            transform::unpremultiply(buffer.to_mut());
+        } else {
+            warning!("Ignoring --unpremultiply for {color_type:?} output");
+        }
+    }
+    if tex2png.auto_unpremultiply
+        && color_type == png::ColorType::Rgba
+        && transform::looks_premultiplied(&buffer)
+    {
+        info!("Alpha looks premultiplied, unpremultiplying it");
+        transform::unpremultiply(buffer.to_mut());
+    }
+    if let Some(threshold) = tex2png.alpha_threshold {
+        if matches!(
+            color_type,
+            png::ColorType::Rgba | png::ColorType::GrayscaleAlpha
+        ) {
+            transform::threshold_alpha(buffer.to_mut(), color_type.samples(), threshold);
+        } else {
+            warning!("Ignoring --alpha-threshold for {color_type:?} output");
+        }
+    }
+    if tex2png.bleed {
+        if color_type == png::ColorType::Rgba {
+            transform::bleed(buffer.to_mut(), width as u32);
+        } else {
+            warning!("Ignoring --bleed for {color_type:?} output");
+        }
+    }
+
+    if let Some(gamma) = tex2png.gamma {
+        let channels = color_type.samples();
+        let has_alpha = matches!(
+            color_type,
+            png::ColorType::Rgba | png::ColorType::GrayscaleAlpha
+        );
+        gamma.apply(buffer.to_mut(), channels, channels - has_alpha as usize);
+    }
+
+    match tex2png.swizzle {
+        Some(swizzle) if color_type == png::ColorType::Rgba => {
+            buffer = Cow::Owned(swizzle.apply(&buffer));
+        }
+        Some(_) => warning!("Ignoring --swizzle for {color_type:?} output"),
+        None => {}
+    }
+
+    buffer
+}
+
+/// Converts every mipmap level for --mipmaps, into the frames of a single animated
+/// PNG with --apng and into `<name>.mip<level>.png` files otherwise.
+fn convert_mipmaps(
+    tex: &[u8],
+    header: &TexHeader,
+    format: TexFormat,
+    out_path: &Path,
+    tex2png: &Tex2Png,
+) -> ExitCode {
+    let pixels = match header.pixels_range(tex.len()) {
+        Ok(range) => &tex[range],
+        Err(error) => {
+            message!("Invalid texture: {error}");
+            return Failure::Malformed.into();
+        }
+    };
+    warn_about_byte_order(format, tex2png);
+
+    let levels = mip_levels(
+        format,
+        header.width as usize,
+        header.height as usize,
+        (header.mipmaps as usize).max(1),
+    );
+    let depth = sample_depth(format);
+    let mut frames = Vec::with_capacity(levels.len());
+    let mut color_type = png::ColorType::Rgba;
+    for (i, level) in levels.iter().enumerate() {
+        let Some(pixels) = pixels.get(level.offset..level.offset + level.size) else {
+            message!("Pixel data is too short to contain mipmap level {i}");
+            return Failure::Malformed.into();
+        };
+
+        let mut decoded = Vec::new();
+        let Some(level_color_type) = decode_into(
+            format,
+            level.width,
+            level.height,
+            pixels,
+            &tex2png.decode_options,
+            &mut decoded,
+        ) else {
+            return Failure::Unsupported.into();
+        };
+        color_type = level_color_type;
+        frames.push(Frame {
+            pixels: Cow::Owned(
+                adjust_colors(&decoded, level.width, color_type, depth, tex2png).into_owned(),
+            ),
+            width: level.width as u32,
+            height: level.height as u32,
+        });
+    }
+
+    if tex2png.verify {
+        return verified();
+    }
+    if tex2png.apng {
+        return write_output(out_path, color_type, depth, &frames, header, tex2png);
+    }
+
+    let extension = out_path.extension().unwrap_or_default().to_string_lossy();
+    let mut result = ExitCode::SUCCESS;
+    for (i, frame) in frames.into_iter().enumerate() {
+        let level_path = out_path.with_extension(format!("mip{i}.{extension}"));
+        let outcome = write_output(&level_path, color_type, depth, &[frame], header, tex2png);
+        keep_first_failure(&mut result, outcome);
+    }
+    result
+}
+
+/// Bit depth of the samples [`decode_into`] produces for `format`.
+fn sample_depth(format: TexFormat) -> png::BitDepth {
+    if format.is_16_bit() {
+        png::BitDepth::Sixteen
+    } else {
+        png::BitDepth::Eight
+    }
+}
+
+/// Warns that formats storing pixels or channels as 16-bit values are assumed to
+/// be little-endian, unless a byte order was chosen.
+fn warn_about_byte_order(format: TexFormat, tex2png: &Tex2Png) {
+    if (format.is_packed_16() || format.is_16_bit())
+        && tex2png.decode_options.pixel_endian.is_none()
+    {
+        warning!(
+            "Assuming little-endian for {} format, pass --pixel-endian to choose",
+            format.name().to_uppercase()
+        );
+    }
+}
+
+/// A single image written by [`write_png`].
+struct Frame<'a> {
+    pixels: Cow<'a, [u8]>,
+    width: u32,
+    height: u32,
+}
+
+/// Writes `frames` to `out_path` in the format chosen for it.
+fn write_output(
+    out_path: &Path,
+    color_type: png::ColorType,
+    depth: png::BitDepth,
+    frames: &[Frame],
+    header: &TexHeader,
+    tex2png: &Tex2Png,
+) -> ExitCode {
+    let format = tex2png.output_format_for(out_path);
+    info!(
+        "Writing {} frame(s) of {}x{} as {format:?} to {}",
+        frames.len(),
+        frames[0].width,
+        frames[0].height,
+        out_path.display()
+    );
+    if tex2png.compare_png && format != OutputFormat::Png {
+        warning!("Ignoring --compare-png for {format:?} output");
+    }
+    let start = Instant::now();
+    let outcome = match format {
+        OutputFormat::Png => write_png(out_path, color_type, depth, frames, header, tex2png),
+        #[cfg(feature = "image-formats")]
+        OutputFormat::Ico => write_ico(out_path, color_type, depth, frames, tex2png),
+        #[cfg(feature = "image-formats")]
+        OutputFormat::Gif => write_gif(out_path, color_type, depth, frames, tex2png),
+        #[cfg(feature = "image-formats")]
+        OutputFormat::Ppm | OutputFormat::Pam => {
+            write_netpbm(out_path, color_type, depth, frames, format, tex2png)
+        }
+        #[cfg(not(feature = "image-formats"))]
+        _ => {
+            message!("Writing {format:?} images is not enabled in this build");
+            Failure::Unsupported.into()
+        }
+    };
+    debug!("Wrote {} in {:.1?}", out_path.display(), start.elapsed());
+    outcome
+}
+
+/// Writes the alpha of `frames` as grayscale to `out_path` and the frames composited
+/// over magenta next to it for --alpha-preview.
+fn write_alpha_preview(
+    out_path: &Path,
+    color_type: png::ColorType,
+    depth: png::BitDepth,
+    frames: &[Frame],
+    header: &TexHeader,
+    tex2png: &Tex2Png,
+) -> ExitCode {
+    const BACKGROUND: transform::Rgb = transform::Rgb([0xFF, 0x00, 0xFF]);
+
+    let rgba = frames
+        .iter()
+        .map(|frame| frame_rgba(frame, color_type, depth))
+        .collect::<Vec<_>>();
+    let preview_frames = |preview: fn(&[u8]) -> Vec<u8>| {
+        frames
+            .iter()
+            .zip(&rgba)
+            .map(|(frame, rgba)| Frame {
+                pixels: Cow::Owned(preview(rgba)),
+                width: frame.width,
+                height: frame.height,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let masks = preview_frames(transform::alpha_to_gray);
+    let mut result = write_output(
+        out_path,
+        png::ColorType::Grayscale,
+        png::BitDepth::Eight,
+        &masks,
+        header,
+        tex2png,
+    );
+    let extension = out_path.extension().unwrap_or_default().to_string_lossy();
+    let composited = preview_frames(|rgba| transform::composite_over(rgba, BACKGROUND));
+    let outcome = write_output(
+        &out_path.with_extension(format!("magenta.{extension}")),
+        png::ColorType::Rgb,
+        png::BitDepth::Eight,
+        &composited,
+        header,
+        tex2png,
+    );
+    keep_first_failure(&mut result, outcome);
+    result
+}
+
+/// Converts the pixels of a frame to 8-bit RGBA.
+fn frame_rgba(frame: &Frame, color_type: png::ColorType, depth: png::BitDepth) -> Vec<u8> {
+    let mut pixels = frame.pixels.to_vec();
+    if depth == png::BitDepth::Sixteen {
+        narrow_to_8_bit(&mut pixels);
+    }
+    png2tex::expand_to_rgba(pixels, color_type)
+}
+
+/// Writes the single frame of `frames` as a PPM or PAM image, keeping the bit depth.
+#[cfg(feature = "image-formats")]
+fn write_netpbm(
+    out_path: &Path,
+    color_type: png::ColorType,
+    depth: png::BitDepth,
+    frames: &[Frame],
+    format: OutputFormat,
+    tex2png: &Tex2Png,
+) -> ExitCode {
+    let [frame] = frames else {
+        message!(
+            "{format:?} images can't be animated, only write a single frame to {}",
+            out_path.display()
+        );
+        return Failure::Unsupported.into();
+    };
+    if tex2png.palette.is_some() {
+        warning!("Ignoring --palette for {format:?} output");
+    }
+
+    let sixteen_bit = depth == png::BitDepth::Sixteen;
+    let channels = color_type.samples();
+    let (width, height) = (frame.width, frame.height);
+    let data = if format == OutputFormat::Pam {
+        netpbm::pam(width, height, channels, sixteen_bit, &frame.pixels)
+    } else {
+        let sample_size = if sixteen_bit { 2 } else { 1 };
+        let has_alpha = matches!(
+            color_type,
+            png::ColorType::Rgba | png::ColorType::GrayscaleAlpha
+        );
+        let opaque = !has_alpha
+            || frame
+                .pixels
+                .chunks_exact(channels * sample_size)
+                .all(|pixel| {
+                    pixel[pixel.len() - sample_size..]
+                        .iter()
+                        .all(|&b| b == 0xFF)
+                });
+        if !opaque {
+            warning!(
+                "Dropping the alpha of {} which isn't opaque, write a PAM image to keep it",
+                out_path.display()
+            );
+        }
+
+        // Grayscale is spread over all three channels.
+        let channel_order = if channels < 3 { [0, 0, 0] } else { [0, 1, 2] };
+        let mut rgb = Vec::with_capacity(width as usize * height as usize * 3 * sample_size);
+        for pixel in frame.pixels.chunks_exact(channels * sample_size) {
+            for i in channel_order {
+                rgb.extend_from_slice(&pixel[i * sample_size..(i + 1) * sample_size]);
+            }
+        }
+        netpbm::ppm(width, height, sixteen_bit, &rgb)
+    };
+
+    let Some(mut file) = create_output(out_path, tex2png.create_dirs()) else {
+        return Failure::Io.into();
+    };
+    if let Err(error) = std::io::Write::write_all(&mut file, &data) {
+        message!("Failed to write {}: {error}", out_path.display());
+        return Failure::Io.into();
+    }
+    ExitCode::SUCCESS
+}
+
+/// Writes `frames` as a GIF, animated if there are several.
+#[cfg(feature = "image-formats")]
+fn write_gif(
+    out_path: &Path,
+    color_type: png::ColorType,
+    depth: png::BitDepth,
+    frames: &[Frame],
+    tex2png: &Tex2Png,
+) -> ExitCode {
+    // GIFs only know fully transparent pixels, so make every other one opaque.
+    let frames_rgba = frames
+        .iter()
+        .map(|frame| {
+            let mut rgba = frame_rgba(frame, color_type, depth);
+            transform::threshold_alpha(&mut rgba, 4, 0x80);
+            rgba
+        })
+        .collect::<Vec<_>>();
+    let has_transparency = frames_rgba
+        .iter()
+        .any(|rgba| rgba.chunks_exact(4).any(|pixel| pixel[3] == 0));
+
+    // One palette entry is left for transparent pixels, which get none of their own.
+    let size = (tex2png.palette.unwrap_or(256) as usize).min(256 - has_transparency as usize);
+    let opaque = frames_rgba
+        .iter()
+        .flat_map(|rgba| rgba.chunks_exact(4).filter(|pixel| pixel[3] != 0))
+        .flatten()
+        .copied()
+        .collect::<Vec<u8>>();
+    let mut palette = palette::Palette::new([&opaque[..]], size);
+    let mut rgb = palette.rgb();
+    let transparent = has_transparency.then(|| {
+        rgb.extend_from_slice(&[0, 0, 0]);
+        (rgb.len() / 3 - 1) as u8
+    });
+
+    let gif_frames = frames
+        .iter()
+        .zip(&frames_rgba)
+        .map(|(frame, rgba)| {
+            // Fully transparent images have no palette colors to look pixels up in.
+            let mut indices = if opaque.is_empty() {
+                vec![0; rgba.len() / 4]
+            } else {
+                palette.index(rgba)
+            };
+            for (index, pixel) in indices.iter_mut().zip(rgba.chunks_exact(4)) {
+                if pixel[3] == 0 {
+                    *index = transparent.unwrap();
+                }
+            }
+            gif::GifFrame {
+                width: frame.width as u16,
+                height: frame.height as u16,
+                indices,
+            }
+        })
+        .collect::<Vec<_>>();
+    let delay = (100.0 / tex2png.fps as f64).round().max(1.0) as u16;
+    let size = (frames[0].width as u16, frames[0].height as u16);
+    let data = gif::encode(size, &gif_frames, &rgb, transparent, delay);
+
+    let Some(mut file) = create_output(out_path, tex2png.create_dirs()) else {
+        return Failure::Io.into();
+    };
+    if let Err(error) = std::io::Write::write_all(&mut file, &data) {
+        message!("Failed to write {}: {error}", out_path.display());
+        return Failure::Io.into();
+    }
+    ExitCode::SUCCESS
+}
+
+/// Writes a single frame as an icon holding it at several sizes.
+#[cfg(feature = "image-formats")]
+fn write_ico(
+    out_path: &Path,
+    color_type: png::ColorType,
+    depth: png::BitDepth,
+    frames: &[Frame],
+    tex2png: &Tex2Png,
+) -> ExitCode {
+    let [frame] = frames else {
+        message!(
+            "Icons can't be animated, only write a single frame to {}",
+            out_path.display()
+        );
+        return Failure::Unsupported.into();
+    };
+    if tex2png.palette.is_some() {
+        warning!("Ignoring --palette for ICO output");
+    }
+
+    let rgba = frame_rgba(frame, color_type, depth);
+    let (square, side) = transform::pad_to_square(&rgba, (frame.width, frame.height), 4);
+
+    let mut images = Vec::new();
+    for size in ico::sizes_for(side) {
+        let resized = resize::resize(
+            &square,
+            (side, side),
+            4,
+            resize::Size {
+                width: size,
+                height: size,
+            },
+            tex2png.filter,
+        );
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, size, size);
+        encoder.set_color(png::ColorType::Rgba);
+        tex2png.png_options.apply(&mut encoder);
+        encoder.set_depth(png::BitDepth::Eight);
+        let result = encoder.write_header().and_then(|mut writer| {
+            writer
+                .write_image_data(&resized)
+                .and_then(|()| writer.finish())
+        });
+        if let Err(error) = result {
+            return encoding_failed(out_path, error);
+        }
+        images.push((size, png));
+    }
+
+    let Some(mut file) = create_output(out_path, tex2png.create_dirs()) else {
+        return Failure::Io.into();
+    };
+    if let Err(error) = std::io::Write::write_all(&mut file, &ico::pack(&images)) {
+        message!("Failed to write {}: {error}", out_path.display());
+        return Failure::Io.into();
+    }
+    ExitCode::SUCCESS
+}
+
+/// Writes `frames` as a PNG, animated if --frames or --apng was used.
+///
+/// The image is as large as the first frame, smaller frames are drawn in its
+/// top left corner on a cleared canvas. Frames with 16-bit samples, as given by
+/// `depth`, are written as they are regardless of --depth.
+fn write_png(
+    out_path: &Path,
+    color_type: png::ColorType,
+    depth: png::BitDepth,
+    frames: &[Frame],
+    header: &TexHeader,
+    tex2png: &Tex2Png,
+) -> ExitCode {
+    let Some(file) = create_output(out_path, tex2png.create_dirs()) else {
+        return Failure::Io.into();
+    };
+    let (width, height) = (frames[0].width, frames[0].height);
+    let mut encoder = png::Encoder::new(file, width, height);
+    if tex2png.frames.is_some() || tex2png.apng {
+        encoder.set_animated(frames.len() as u32, 0).unwrap();
+        encoder.set_frame_delay(1, tex2png.fps).unwrap();
+        if frames
+            .iter()
+            .any(|frame| (frame.width, frame.height) != (width, height))
+        {
+            encoder.set_dispose_op(png::DisposeOp::Background).unwrap();
+        }
+    }
+    let mut palette = match tex2png.palette {
+        Some(size) if color_type == png::ColorType::Rgba && depth == png::BitDepth::Eight => {
+            let frame_pixels = frames.iter().map(|frame| &*frame.pixels);
+            Some(palette::Palette::new(frame_pixels, size as usize))
+        }
+        Some(_) => {
+            warning!("Ignoring --palette for {color_type:?} output");
+            None
+        }
+        None => None,
+    };
+    match &palette {
+        Some(palette) => {
+            encoder.set_color(png::ColorType::Indexed);
+            encoder.set_palette(palette.rgb());
+            let alpha = palette.alpha();
+            if !alpha.is_empty() {
+                encoder.set_trns(alpha);
+            }
+        }
+        None => encoder.set_color(color_type),
+    }
+    match tex2png.gamma.filter(|_| depth == png::BitDepth::Eight) {
+        Some(transform::Gamma::Srgb) => tag_srgb(&mut encoder),
+        Some(transform::Gamma::Linear) => encoder.set_source_gamma(png::ScaledFloat::new(1.0)),
+        Some(transform::Gamma::Power(gamma)) => {
+            encoder.set_source_gamma(png::ScaledFloat::new(1.0 / gamma as f32))
+        }
+        None if tex2png.srgb => tag_srgb(&mut encoder),
+        None => {}
+    }
+    tex2png.png_options.apply(&mut encoder);
+    if depth == png::BitDepth::Sixteen {
+        encoder.set_depth(depth);
+    }
+    if tex2png.embed_metadata {
+        let format = match TexFormat::from_value(header.format) {
+            Some(format) => format.name().to_string(),
+            None => header.format.to_string(),
+        };
+        for (keyword, value) in [
+            ("siltex:format", format),
+            ("siltex:version", header.version.to_string()),
+            ("siltex:scale", header.scale.to_string()),
+        ] {
+            encoder.add_text_chunk(keyword.to_string(), value).unwrap();
+        }
+        if header.scale > 0 {
+            encoder.set_pixel_dims(Some(png::PixelDimensions {
+                xppu: header.scale as u32,
+                yppu: header.scale as u32,
+                unit: png::Unit::Unspecified,
+            }));
+        }
+    }
+    // The image data of every frame as encoded, kept for --compare-png.
+    let mut encoded = Vec::new();
+    let result = encoder.write_header().and_then(|mut writer| {
+        for frame in frames {
+            if (frame.width, frame.height) != (width, height) {
+                writer.set_frame_dimension(frame.width, frame.height)?;
+            }
+            let data = if let Some(palette) = &mut palette {
+                Cow::Owned(palette.index(&frame.pixels))
+            } else if depth == png::BitDepth::Eight
+                && tex2png.png_options.depth == PngDepth::Sixteen
+            {
+                // 16-bit samples are big-endian, replicating the byte maps 0xFF to 0xFFFF.
+                Cow::Owned(frame.pixels.iter().flat_map(|&v| [v, v]).collect())
+            } else {
+                Cow::Borrowed(&*frame.pixels)
+            };
+            writer.write_image_data(&data)?;
+            if tex2png.compare_png {
+                encoded.push(data);
+            }
+        }
+        writer.finish()
+    });
+
+    match result {
+        Ok(()) if tex2png.compare_png => compare_png(out_path, &encoded),
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => encoding_failed(out_path, error),
+    }
+}
+
+/// Marks a PNG as sRGB, along with the gAMA and cHRM values the PNG specification
+/// recommends for readers that don't understand the sRGB chunk.
+fn tag_srgb<W: std::io::Write>(encoder: &mut png::Encoder<W>) {
+    let point = |x, y| {
+        (
+            png::ScaledFloat::from_scaled(x),
+            png::ScaledFloat::from_scaled(y),
+        )
+    };
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+    encoder.set_source_gamma(png::ScaledFloat::from_scaled(45455));
+    encoder.set_source_chromaticities(png::SourceChromaticities {
+        white: point(31270, 32900),
+        red: point(64000, 33000),
+        green: point(30000, 60000),
+        blue: point(15000, 6000),
+    });
+}
+
+/// Reads back the PNG written to `out_path` for --compare-png and checks that its
+/// frames hold exactly the `encoded` image data, without any transformations.
+fn compare_png(out_path: &Path, encoded: &[Cow<[u8]>]) -> ExitCode {
+    let read_frames = || -> Result<Vec<Vec<u8>>, png::DecodingError> {
+        let file = std::io::BufReader::new(std::fs::File::open(out_path)?);
+        let mut decoder = png::Decoder::new(file);
+        decoder.set_transformations(png::Transformations::IDENTITY);
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let mut frames = Vec::with_capacity(encoded.len());
+        for _ in encoded {
+            let info = reader.next_frame(&mut buffer)?;
+            frames.push(buffer[..info.buffer_size()].to_vec());
+        }
+        Ok(frames)
+    };
+
+    let frames = match read_frames() {
+        Ok(frames) => frames,
+        Err(error) => {
+            message!("Failed to read back {}: {error}", out_path.display());
+            return match error {
+                png::DecodingError::IoError(_) => Failure::Io.into(),
+                _ => Failure::Encode.into(),
+            };
+        }
+    };
+    for (i, (read, encoded)) in frames.iter().zip(encoded).enumerate() {
+        if read[..] == encoded[..] {
+            continue;
+        }
+        let offset = read
+            .iter()
+            .zip(encoded.iter())
+            .position(|(read, encoded)| read != encoded)
+            .unwrap_or(read.len().min(encoded.len()));
+        message!(
+            "{} reads back differently than it was written: frame {i} differs from byte {offset} on",
+            out_path.display()
+        );
+        return Failure::Encode.into();
+    }
+    debug!(
+        "Read back {} frame(s) of {}",
+        frames.len(),
+        out_path.display()
+    );
+    ExitCode::SUCCESS
+}
+
+/// Reports an error of the PNG encoder, an I/O error if the output couldn't be
+/// written and an encoding error otherwise.
+fn encoding_failed(out_path: &Path, error: png::EncodingError) -> ExitCode {
+    message!("Failed to write {}: {error}", out_path.display());
+    match error {
+        png::EncodingError::IoError(_) => Failure::Io.into(),
+        _ => Failure::Encode.into(),
+    }
+}
@@ -0,0 +1,31 @@
+//! Writers for the PPM and PAM formats of Netpbm, which are a short text header
+//! followed by the raw samples, with 16-bit samples stored big-endian.
+
+/// Largest sample value for samples of one or two bytes.
+fn maxval(sixteen_bit: bool) -> u32 {
+    if sixteen_bit {
+        0xFFFF
+    } else {
+        0xFF
+    }
+}
+
+/// Encodes RGB samples of a `width` by `height` image as a binary PPM (P6) image.
+pub fn ppm(width: u32, height: u32, sixteen_bit: bool, rgb: &[u8]) -> Vec<u8> {
+    let mut ppm = format!("P6\n{width} {height}\n{}\n", maxval(sixteen_bit)).into_bytes();
+    ppm.extend_from_slice(rgb);
+    ppm
+}
+
+/// Encodes samples of a `width` by `height` image with `channels` channels, which
+/// are grayscale, grayscale and alpha, RGB or RGBA, as a PAM (P7) image.
+pub fn pam(width: u32, height: u32, channels: usize, sixteen_bit: bool, samples: &[u8]) -> Vec<u8> {
+    let tuple_type = ["GRAYSCALE", "GRAYSCALE_ALPHA", "RGB", "RGB_ALPHA"][channels - 1];
+    let mut pam = format!(
+        "P7\nWIDTH {width}\nHEIGHT {height}\nDEPTH {channels}\nMAXVAL {}\nTUPLTYPE {tuple_type}\nENDHDR\n",
+        maxval(sixteen_bit)
+    )
+    .into_bytes();
+    pam.extend_from_slice(samples);
+    pam
+}
@@ -0,0 +1,132 @@
+//! Color quantization for indexed PNG output.
+
+use std::collections::HashMap;
+
+/// Up to 256 RGBA colors that the pixels of an image are mapped onto.
+pub struct Palette {
+    colors: Vec<[u8; 4]>,
+    /// Index of the closest palette color for every color already looked up.
+    indices: HashMap<[u8; 4], u8>,
+}
+
+/// A distinct color and how many pixels have it.
+type Weighted = ([u8; 4], u64);
+
+/// Difference between the largest and smallest value of `channel` in a box.
+fn channel_range(colors: &[Weighted], channel: usize) -> u8 {
+    let values = colors.iter().map(|(color, _)| color[channel]);
+    values.clone().max().unwrap_or(0) - values.min().unwrap_or(0)
+}
+
+/// Mean color of a box, weighted by how many pixels have each color.
+fn average(colors: &[Weighted]) -> [u8; 4] {
+    let total = colors.iter().map(|&(_, count)| count).sum::<u64>();
+    std::array::from_fn(|channel| {
+        let sum = colors
+            .iter()
+            .map(|&(color, count)| color[channel] as u64 * count)
+            .sum::<u64>();
+        ((sum + total / 2) / total) as u8
+    })
+}
+
+impl Palette {
+    /// Picks at most `size` colors representing the RGBA pixels of all `images`
+    /// with median cut, `size` must be between 1 and 256.
+    ///
+    /// Images with no more than `size` distinct colors keep every one of them.
+    pub fn new<'a>(images: impl IntoIterator<Item = &'a [u8]>, size: usize) -> Palette {
+        debug_assert!((1..=256).contains(&size));
+        let mut counts = HashMap::<[u8; 4], u64>::new();
+        for image in images {
+            for pixel in image.chunks_exact(4) {
+                *counts.entry(pixel.try_into().unwrap()).or_default() += 1;
+            }
+        }
+        // Sorted so the palette doesn't depend on the iteration order of the map.
+        let mut distinct = counts.into_iter().collect::<Vec<Weighted>>();
+        distinct.sort_unstable();
+
+        let mut colors = if distinct.len() <= size {
+            distinct.into_iter().map(|(color, _)| color).collect()
+        } else {
+            let mut boxes = vec![distinct];
+            while boxes.len() < size {
+                // Split the box spanning the largest range of any channel at the
+                // pixel-weighted median of that channel.
+                let Some((index, channel, _)) = boxes
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, colors)| colors.len() > 1)
+                    .flat_map(|(i, colors)| (0..4).map(move |c| (i, c, channel_range(colors, c))))
+                    .max_by_key(|&(_, _, range)| range)
+                else {
+                    break;
+                };
+
+                let mut colors = boxes.swap_remove(index);
+                colors.sort_by_key(|(color, _)| color[channel]);
+                let half = colors.iter().map(|&(_, count)| count).sum::<u64>() / 2;
+                let mut seen = 0;
+                let median = colors
+                    .iter()
+                    .position(|&(_, count)| {
+                        seen += count;
+                        seen > half
+                    })
+                    .unwrap_or(0)
+                    .clamp(1, colors.len() - 1);
+                let upper = colors.split_off(median);
+                boxes.push(colors);
+                boxes.push(upper);
+            }
+            boxes
+                .iter()
+                .map(|colors| average(colors))
+                .collect::<Vec<_>>()
+        };
+        // Translucent colors go first so the tRNS chunk can stop after them.
+        colors.sort_by_key(|color| color[3] == 0xFF);
+
+        Palette {
+            colors,
+            indices: HashMap::new(),
+        }
+    }
+
+    /// Red, green and blue of every color, as stored in the PLTE chunk.
+    pub fn rgb(&self) -> Vec<u8> {
+        self.colors
+            .iter()
+            .flat_map(|color| &color[..3])
+            .copied()
+            .collect()
+    }
+
+    /// Alpha of every color up to the last translucent one, as stored in the tRNS
+    /// chunk, which is empty if the palette is opaque.
+    pub fn alpha(&self) -> Vec<u8> {
+        let translucent = self.colors.iter().take_while(|color| color[3] != 0xFF);
+        translucent.map(|color| color[3]).collect()
+    }
+
+    /// Maps RGBA pixels to the indices of the closest palette colors.
+    pub fn index(&mut self, rgba: &[u8]) -> Vec<u8> {
+        rgba.chunks_exact(4)
+            .map(|pixel| {
+                let pixel: [u8; 4] = pixel.try_into().unwrap();
+                *self.indices.entry(pixel).or_insert_with(|| {
+                    let distance = |color: &[u8; 4]| {
+                        (0..4)
+                            .map(|c| (color[c] as i32 - pixel[c] as i32).pow(2))
+                            .sum::<i32>()
+                    };
+                    let closest = (0..self.colors.len())
+                        .min_by_key(|&i| distance(&self.colors[i]))
+                        .unwrap();
+                    closest as u8
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,473 @@
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Instant,
+};
+
+use clap::ValueEnum;
+
+use crate::{
+    full_mip_count, mip_levels, transform, Failure, PixelEndian, TexFormat, TexHeader, TexImage,
+    MAGIC,
+};
+
+#[derive(clap::Parser)]
+pub struct Png2Tex {
+    png_path: PathBuf,
+    #[clap(short = 'o', long = "output")]
+    output_path: Option<PathBuf>,
+    /// Pixel format of the generated texture, defaults to the one recorded by
+    /// `tex2png --embed-metadata` or bgra8888
+    #[clap(long, value_enum)]
+    format: Option<TexFormat>,
+    /// Value stored in the scale field of the header, defaults to the one recorded by
+    /// `tex2png --embed-metadata` or 1
+    #[clap(long)]
+    scale: Option<i32>,
+    /// Multiply colors by their alpha before storing them
+    ///
+    /// This is the inverse of `tex2png --unpremultiply`: converting a premultiplied
+    /// 8888 texture to PNG with it and back with this reproduces the original pixels.
+    #[clap(long)]
+    premultiply: bool,
+    /// Fill the color of fully transparent pixels with that of the nearest visible
+    /// one, which avoids dark fringes when the texture is filtered or mipmapped
+    ///
+    /// Premultiplied colors of transparent pixels are always zero, so this can't be
+    /// combined with --premultiply.
+    #[clap(long, conflicts_with = "premultiply")]
+    bleed: bool,
+    /// Don't store the bitmap marking fully opaque texels
+    #[clap(long)]
+    no_bitmap: bool,
+    /// Generate a full mipmap chain down to 1x1 by repeatedly box filtering the image
+    #[clap(long)]
+    generate_mips: bool,
+    /// Spread the rounding error of 4 and 5-bit channels over neighbouring pixels
+    /// with Floyd-Steinberg dithering, which hides banding in gradients
+    ///
+    /// Has no effect on formats with 8-bit channels.
+    #[clap(long)]
+    dither: bool,
+    /// Byte order of formats packing a pixel into 16 bits (5551, 4444)
+    #[clap(long, value_enum, default_value_t = PixelEndian::Little)]
+    pixel_endian: PixelEndian,
+}
+
+/// Header fields recorded in the text chunks written by `tex2png --embed-metadata`.
+#[derive(Default)]
+struct SourceMetadata {
+    format: Option<u8>,
+    version: Option<u8>,
+    scale: Option<i32>,
+}
+
+impl SourceMetadata {
+    fn from_text_chunks(chunks: &[png::text_metadata::TEXtChunk]) -> Self {
+        let mut metadata = SourceMetadata::default();
+        for chunk in chunks {
+            match chunk.keyword.as_str() {
+                "siltex:format" => metadata.format = parse_format_name(&chunk.text),
+                "siltex:version" => metadata.version = chunk.text.parse().ok(),
+                "siltex:scale" => metadata.scale = chunk.text.parse().ok(),
+                _ => {}
+            }
+        }
+        metadata
+    }
+}
+
+/// Parses a recorded format, either the name of a known format or the value of an
+/// unknown one.
+fn parse_format_name(text: &str) -> Option<u8> {
+    TexFormat::value_variants()
+        .iter()
+        .find(|format| {
+            // Older versions recorded the variant name, like `Pvrtc4Rgb`.
+            format.name() == text || format!("{format:?}").eq_ignore_ascii_case(text)
+        })
+        .map(|&format| format.into())
+        .or_else(|| text.parse().ok())
+}
+
+/// Reads a PNG file and converts it to 8-bit RGBA, telling I/O errors apart from
+/// invalid files.
+pub fn read_png(path: &Path) -> Result<TexImage, (Failure, String)> {
+    read_png_with_metadata(path).map(|(image, _)| image)
+}
+
+/// Like [`read_png`], but also returns the header fields recorded by tex2png.
+fn read_png_with_metadata(path: &Path) -> Result<(TexImage, SourceMetadata), (Failure, String)> {
+    let decoding_failed = |error: png::DecodingError| match error {
+        png::DecodingError::IoError(error) => (Failure::Io, error.to_string()),
+        error => (Failure::Malformed, error.to_string()),
+    };
+    let file = std::fs::File::open(path).map_err(|e| (Failure::Io, e.to_string()))?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(decoding_failed)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(decoding_failed)?;
+    buffer.truncate(info.buffer_size());
+
+    let metadata = SourceMetadata::from_text_chunks(&reader.info().uncompressed_latin1_text);
+    let image = TexImage {
+        width: info.width,
+        height: info.height,
+        rgba: expand_to_rgba(buffer, info.color_type),
+    };
+    Ok((image, metadata))
+}
+
+/// Converts 8-bit pixels of any non-palette color type to RGBA.
+pub fn expand_to_rgba(buffer: Vec<u8>, color_type: png::ColorType) -> Vec<u8> {
+    match color_type {
+        png::ColorType::Rgba => buffer,
+        png::ColorType::Rgb => buffer
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 0xFF])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buffer
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => buffer.iter().flat_map(|&l| [l, l, l, 0xFF]).collect(),
+        png::ColorType::Indexed => unreachable!("palette images are expanded by the decoder"),
+    }
+}
+
+/// Halves an RGBA image by averaging every 2x2 block of pixels.
+///
+/// Odd dimensions are handled by clamping, so the last row or column gets
+/// averaged with itself.
+fn downscale(image: &TexImage) -> TexImage {
+    let (width, height) = (image.width as usize, image.height as usize);
+    let (new_width, new_height) = ((width / 2).max(1), (height / 2).max(1));
+    let mut result = Vec::with_capacity(new_width * new_height * 4);
+    for y in 0..new_height {
+        let (y0, y1) = (2 * y, (2 * y + 1).min(height - 1));
+        for x in 0..new_width {
+            let (x0, x1) = (2 * x, (2 * x + 1).min(width - 1));
+            for channel in 0..4 {
+                let sum = [(x0, y0), (x1, y0), (x0, y1), (x1, y1)]
+                    .into_iter()
+                    .map(|(x, y)| image.rgba[(y * width + x) * 4 + channel] as u32)
+                    .sum::<u32>();
+                result.push(((sum + 2) / 4) as u8);
+            }
+        }
+    }
+    TexImage {
+        width: new_width as u32,
+        height: new_height as u32,
+        rgba: result,
+    }
+}
+
+/// Scales an 8-bit channel value down to `bits` bits with rounding.
+fn quantize(value: u8, bits: u32) -> u16 {
+    let max = (1 << bits) - 1;
+    ((value as u32 * max + 127) / 255) as u16
+}
+
+/// Expands a `bits` bit channel value to 8 bits rounding down, which is what
+/// `tex2png` decodes a value stored by [`quantize`] to.
+fn dequantize(value: u16, bits: u32) -> u8 {
+    let max = (1 << bits) - 1;
+    (value as u32 * 255 / max) as u8
+}
+
+/// Number of bits `format` stores of each RGBA channel when they are reduced
+/// from 8 bits, `None` for channels that are stored as is or thresholded.
+fn reduced_bits(format: TexFormat) -> [Option<u32>; 4] {
+    match format {
+        TexFormat::Bgra5551 => [Some(5), Some(5), Some(5), None],
+        TexFormat::Bgra4444 | TexFormat::Rgba4444 => [Some(4); 4],
+        _ => [None; 4],
+    }
+}
+
+/// Applies Floyd-Steinberg dithering to the channels `format` stores with fewer bits.
+///
+/// Every channel is replaced by the 8-bit value of the level it will be stored as,
+/// with the difference carried over to the pixels right of and below it.
+fn dither(format: TexFormat, image: &TexImage) -> TexImage {
+    let bits = reduced_bits(format);
+    let (width, height) = (image.width as usize, image.height as usize);
+    let mut rgba = image.rgba.clone();
+    // Errors carried into the current and the next row, with a pixel of padding on both sides.
+    let mut current = vec![[0i32; 4]; width + 2];
+    let mut next = vec![[0i32; 4]; width + 2];
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = &mut rgba[(y * width + x) * 4..][..4];
+            for channel in 0..4 {
+                let Some(bits) = bits[channel] else {
+                    continue;
+                };
+                let value = (pixel[channel] as i32 + current[x + 1][channel] / 16).clamp(0, 255);
+                let stored = dequantize(quantize(value as u8, bits), bits);
+                let error = value - stored as i32;
+                pixel[channel] = stored;
+
+                current[x + 2][channel] += error * 7;
+                next[x][channel] += error * 3;
+                next[x + 1][channel] += error * 5;
+                next[x + 2][channel] += error;
+            }
+        }
+        std::mem::swap(&mut current, &mut next);
+        next.fill([0; 4]);
+    }
+
+    TexImage {
+        width: image.width,
+        height: image.height,
+        rgba,
+    }
+}
+
+/// Converts RGBA pixels to `format` and appends them to `out`.
+///
+/// This is the inverse of the decoding done by `tex2png` with the same `endian`.
+pub fn encode_pixels(format: TexFormat, rgba: &[u8], endian: PixelEndian, out: &mut Vec<u8>) {
+    for pixel in rgba.chunks_exact(4) {
+        let [r, g, b, a] = [pixel[0], pixel[1], pixel[2], pixel[3]];
+        match format {
+            TexFormat::A8 => out.push(a),
+            TexFormat::L8 => out.push(luminance(r, g, b)),
+            TexFormat::La88 => out.extend_from_slice(&[luminance(r, g, b), a]),
+            TexFormat::Bgr888 => out.extend_from_slice(&[b, g, r]),
+            TexFormat::Bgra8888 => out.extend_from_slice(&[b, g, r, a]),
+            TexFormat::Bgra5551 => {
+                let value = ((a >= 0x80) as u16) << 15
+                    | quantize(r, 5) << 10
+                    | quantize(g, 5) << 5
+                    | quantize(b, 5);
+                out.extend_from_slice(&endian.write_u16(value));
+            }
+            TexFormat::Bgra4444 => {
+                let value = quantize(a, 4) << 12
+                    | quantize(r, 4) << 8
+                    | quantize(g, 4) << 4
+                    | quantize(b, 4);
+                out.extend_from_slice(&endian.write_u16(value));
+            }
+            TexFormat::Rgba4444 => {
+                let value = quantize(r, 4) << 12
+                    | quantize(g, 4) << 8
+                    | quantize(b, 4) << 4
+                    | quantize(a, 4);
+                out.extend_from_slice(&endian.write_u16(value));
+            }
+            TexFormat::Pvrtc2Rgba
+            | TexFormat::Pvrtc4Rgba
+            | TexFormat::Pvrtc2Rgb
+            | TexFormat::Pvrtc4Rgb
+            | TexFormat::Etc2Rgb
+            | TexFormat::Etc2Rgba
+            | TexFormat::Rgba16 => unreachable!("{format:?} is not encodable"),
+        }
+    }
+}
+
+/// Packs one bit per pixel, set if the pixel is fully opaque.
+///
+/// Bits are stored in row-major order starting from the least significant bit
+/// of each byte, rows are not padded.
+pub fn opaque_bitmap(rgba: &[u8]) -> Vec<u8> {
+    let mut bitmap = vec![0; (rgba.len() / 4).div_ceil(8)];
+    for (i, pixel) in rgba.chunks_exact(4).enumerate() {
+        if pixel[3] == 0xFF {
+            bitmap[i / 8] |= 1 << (i % 8);
+        }
+    }
+    bitmap
+}
+
+/// Header fields of a texture that don't describe where its data is stored.
+pub struct TexLayout {
+    pub version: u8,
+    pub format: u8,
+    pub mipmaps: u8,
+    pub width: i16,
+    pub height: i16,
+    pub scale: i32,
+}
+
+/// Size of a texture file holding `pixels_size` bytes of pixels and a bitmap of
+/// `bitmap_size` bytes, `None` if it doesn't fit the header's 32-bit sizes.
+pub fn tex_file_size(pixels_size: usize, bitmap_size: usize) -> Option<i32> {
+    TexHeader::SIZE
+        .checked_add(pixels_size)?
+        .checked_add(bitmap_size)?
+        .try_into()
+        .ok()
+}
+
+/// Builds a texture file from header fields, the encoded pixels of every level and
+/// optionally the opaque bitmap, which are stored right after the header.
+///
+/// Returns `None` if the data is too large for the offsets and sizes of the header.
+pub fn assemble_tex(layout: TexLayout, pixels: &[u8], bitmap: Option<&[u8]>) -> Option<Vec<u8>> {
+    let bitmap_size = bitmap.map_or(0, <[u8]>::len);
+    tex_file_size(pixels.len(), bitmap_size)?;
+    let header = TexHeader {
+        magic: MAGIC,
+        version: layout.version,
+        format: layout.format,
+        mipmaps: layout.mipmaps,
+        opaque_bitmap: bitmap.is_some() as u8,
+        width: layout.width,
+        height: layout.height,
+        scale: layout.scale,
+        pixels_offset: TexHeader::SIZE as i32,
+        pixels_size: i32::try_from(pixels.len()).ok()?,
+        bitmap_offset: match bitmap {
+            Some(_) => i32::try_from(TexHeader::SIZE + pixels.len()).ok()?,
+            None => 0,
+        },
+        bitmap_size: i32::try_from(bitmap_size).ok()?,
+    };
+
+    let mut tex = Vec::with_capacity(TexHeader::SIZE + pixels.len() + bitmap_size);
+    tex.extend_from_slice(&header.to_bytes());
+    tex.extend_from_slice(pixels);
+    tex.extend_from_slice(bitmap.unwrap_or_default());
+    Some(tex)
+}
+
+/// Rec. 601 luma of an RGB color, exact for gray inputs.
+fn luminance(r: u8, g: u8, b: u8) -> u8 {
+    ((77 * r as u32 + 150 * g as u32 + 29 * b as u32 + 128) >> 8) as u8
+}
+
+pub fn png2tex(args: &Png2Tex) -> ExitCode {
+    if let Some(format) = args.format.filter(|format| !format.is_encodable()) {
+        eprintln!("Conversion to {format:?} is not implemented yet");
+        return Failure::Unsupported.into();
+    }
+
+    let out_path = args.output_path.clone().unwrap_or_else(|| {
+        args.png_path
+            .strip_prefix(args.png_path.parent().unwrap())
+            .unwrap()
+            .with_extension("tex")
+    });
+
+    let (mut image, metadata) = match read_png_with_metadata(&args.png_path) {
+        Ok(result) => result,
+        Err((failure, error)) => {
+            eprintln!("Failed to read {}: {error}", args.png_path.display());
+            return failure.into();
+        }
+    };
+
+    let format = args.format.unwrap_or_else(|| {
+        let Some(value) = metadata.format else {
+            return TexFormat::Bgra8888;
+        };
+        match TexFormat::from_value(value) {
+            Some(format) if format.is_encodable() => format,
+            _ => {
+                warning!("Recorded format 0x{value:02X} can't be encoded, using Bgra8888");
+                TexFormat::Bgra8888
+            }
+        }
+    });
+
+    let (width, height) = (image.width as usize, image.height as usize);
+    info!("Encoding {width}x{height} pixels as {format:?}");
+    if width > i16::MAX as usize || height > i16::MAX as usize {
+        eprintln!("Image is too large for a tex file: {width}x{height}");
+        return Failure::Encode.into();
+    }
+
+    if args.bleed {
+        transform::bleed(&mut image.rgba, image.width);
+    }
+    if args.premultiply {
+        transform::premultiply(&mut image.rgba);
+    }
+
+    let level_count = if args.generate_mips {
+        full_mip_count(width, height)
+    } else {
+        1
+    };
+    let levels = mip_levels(format, width, height, level_count);
+    let pixels_size = levels.iter().map(|level| level.size).sum();
+    let bitmap_size = if args.no_bitmap {
+        0
+    } else {
+        (width * height).div_ceil(8)
+    };
+    if tex_file_size(pixels_size, bitmap_size).is_none() {
+        eprintln!(
+            "Image is too large for a tex file: {pixels_size} bytes of {format:?} pixels \
+             don't fit its 32-bit sizes"
+        );
+        return Failure::Unsupported.into();
+    }
+    let start = Instant::now();
+
+    let bitmap = if args.no_bitmap {
+        Vec::new()
+    } else {
+        opaque_bitmap(image.as_rgba_bytes())
+    };
+
+    let mut pixels = Vec::with_capacity(pixels_size);
+    for (i, level) in levels.iter().enumerate() {
+        if i > 0 {
+            image = downscale(&image);
+        }
+        debug_assert_eq!(
+            (image.width as usize, image.height as usize),
+            (level.width, level.height)
+        );
+        debug_assert_eq!(pixels.len(), level.offset);
+        if args.dither {
+            encode_pixels(
+                format,
+                dither(format, &image).as_rgba_bytes(),
+                args.pixel_endian,
+                &mut pixels,
+            );
+        } else {
+            encode_pixels(
+                format,
+                image.as_rgba_bytes(),
+                args.pixel_endian,
+                &mut pixels,
+            );
+        }
+    }
+
+    debug!(
+        "Encoded {level_count} mipmap levels into {} bytes in {:.1?}",
+        pixels.len(),
+        start.elapsed()
+    );
+
+    let tex = assemble_tex(
+        TexLayout {
+            version: metadata.version.unwrap_or(2),
+            format: format.into(),
+            mipmaps: level_count as u8,
+            width: width as i16,
+            height: height as i16,
+            scale: args.scale.or(metadata.scale).unwrap_or(1),
+        },
+        &pixels,
+        (!args.no_bitmap).then_some(&bitmap[..]),
+    )
+    .expect("the size of the texture was checked before encoding");
+    info!("Writing {} bytes to {}", tex.len(), out_path.display());
+    if let Err(error) = std::fs::write(&out_path, tex) {
+        eprintln!("Failed to write {}: {error}", out_path.display());
+        return Failure::Io.into();
+    }
+
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,119 @@
+use std::{
+    io::Write,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+/// A single line progress bar drawn on stderr.
+///
+/// The cursor is left at the start of the bar's line, so messages printed while
+/// it is shown overwrite it and the bar is redrawn below them on the next update.
+pub struct Progress {
+    total: usize,
+    done: usize,
+    start: Instant,
+}
+
+/// Formats a duration as `M:SS`, or `H:MM:SS` once it exceeds an hour.
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+impl Progress {
+    const WIDTH: usize = 30;
+
+    pub fn new(total: usize) -> Self {
+        Progress {
+            total,
+            done: 0,
+            start: Instant::now(),
+        }
+    }
+
+    /// Draws the bar with `path` as the file currently being converted.
+    pub fn show(&self, path: &Path) {
+        let filled = Self::WIDTH * self.done / self.total.max(1);
+        let eta = if self.done == 0 {
+            "-:--".to_string()
+        } else {
+            let remaining = (self.total - self.done) as u32;
+            format_duration(self.start.elapsed() / self.done as u32 * remaining)
+        };
+
+        let mut stderr = std::io::stderr().lock();
+        _ = write!(
+            stderr,
+            "\x1b[2K[{}{}] {}/{} ETA {eta} {}\r",
+            "=".repeat(filled),
+            " ".repeat(Self::WIDTH - filled),
+            self.done,
+            self.total,
+            path.display()
+        );
+        _ = stderr.flush();
+    }
+
+    /// Marks the current file as converted.
+    pub fn advance(&mut self) {
+        self.done += 1;
+    }
+
+    /// Removes the bar from the terminal.
+    pub fn finish(&self) {
+        eprint!("\x1b[2K");
+    }
+}
+
+/// Progress through the rows of a single large image, shown as `<label> row <done>/<total>`.
+///
+/// Nothing is drawn for steps finishing within the first update interval, and
+/// later updates are throttled to a few per second.
+pub struct RowProgress {
+    label: &'static str,
+    total: usize,
+    last_update: Instant,
+    shown: bool,
+}
+
+impl RowProgress {
+    const INTERVAL: Duration = Duration::from_millis(250);
+
+    pub fn new(label: &'static str, total: usize) -> Self {
+        RowProgress {
+            label,
+            total,
+            last_update: Instant::now(),
+            shown: false,
+        }
+    }
+
+    /// Reports that the first `done` rows are finished.
+    pub fn update(&mut self, done: usize) {
+        if self.last_update.elapsed() < Self::INTERVAL {
+            return;
+        }
+
+        let mut stderr = std::io::stderr().lock();
+        _ = write!(stderr, "\x1b[2K{} row {done}/{}\r", self.label, self.total);
+        _ = stderr.flush();
+        self.last_update = Instant::now();
+        self.shown = true;
+    }
+
+    /// Removes the progress line if it was drawn.
+    pub fn finish(&self) {
+        if self.shown {
+            eprint!("\x1b[2K");
+        }
+    }
+}
@@ -0,0 +1,255 @@
+//! Decoder for PVRTC textures with 2 and 4 bits per pixel.
+//!
+//! Both variants store 64-bit blocks in Morton order, of 8x4 pixels for 2 bits per
+//! pixel and 4x4 pixels for 4 bits per pixel. Every block holds two colors and a
+//! modulation value per pixel: the colors of neighbouring blocks are bilinearly
+//! upscaled into two full size images, which the modulation values blend between.
+
+/// Weights out of 8 of color B for the two bit modulation values.
+const WEIGHTS: [u8; 4] = [0, 3, 5, 8];
+/// Set on modulation weights of punch-through pixels, which are fully transparent.
+const PUNCH_THROUGH: u8 = 0x10;
+/// Marks modulation weights of the 2bpp interpolated mode that aren't stored.
+const INTERPOLATED: u8 = 0xFF;
+
+/// A color with 5 bits per color channel and 4 bits of alpha.
+type Color = [u32; 4];
+
+struct Block {
+    modulation: u32,
+    /// Punch-through alpha for 4bpp and interpolated modulation for 2bpp blocks.
+    alternate_mode: bool,
+    a: Color,
+    b: Color,
+}
+
+impl Block {
+    fn parse(block: &[u8]) -> Block {
+        let modulation = u32::from_le_bytes(block[..4].try_into().unwrap());
+        let colors = u32::from_le_bytes(block[4..8].try_into().unwrap());
+        // Both colors are either opaque RGB 554 (A) or 555 (B), or ARGB 3443 (A)
+        // or 3444 (B), and get widened to RGB 555 with 4 bits of alpha.
+        let a = colors & 0xFFFF;
+        let a = if a & 0x8000 != 0 {
+            [
+                a >> 10 & 0x1F,
+                a >> 5 & 0x1F,
+                (a & 0x1E) | (a >> 4 & 1),
+                0xF,
+            ]
+        } else {
+            [
+                (a >> 7 & 0x1E) | (a >> 11 & 1),
+                (a >> 3 & 0x1E) | (a >> 7 & 1),
+                (a << 1 & 0x1C) | (a >> 2 & 3),
+                a >> 11 & 0xE,
+            ]
+        };
+        let b = colors >> 16;
+        let b = if b & 0x8000 != 0 {
+            [b >> 10 & 0x1F, b >> 5 & 0x1F, b & 0x1F, 0xF]
+        } else {
+            [
+                (b >> 7 & 0x1E) | (b >> 11 & 1),
+                (b >> 3 & 0x1E) | (b >> 7 & 1),
+                (b << 1 & 0x1E) | (b >> 3 & 1),
+                b >> 11 & 0xE,
+            ]
+        };
+
+        Block {
+            modulation,
+            alternate_mode: colors & 1 != 0,
+            a,
+            b,
+        }
+    }
+}
+
+/// Position of the block at `x`, `y` in the pixel data, with the bits of `y` and
+/// `x` interleaved as long as both dimensions have them, `y` first, followed by
+/// the remaining bits of the larger dimension.
+fn morton_index(x: usize, y: usize, min_dimension: usize) -> usize {
+    let mut index = 0;
+    let mut bit = 0;
+    while 1 << bit < min_dimension {
+        index |= ((y >> bit & 1) | (x >> bit & 1) << 1) << (2 * bit);
+        bit += 1;
+    }
+    index | ((x | y) >> bit) << (2 * bit)
+}
+
+/// Modulation weights of the pixels of `block`, row by row, with stored weights
+/// of the 2bpp interpolated mode left as [`INTERPOLATED`].
+fn block_weights(block: &Block, two_bit: bool) -> Vec<u8> {
+    let mut bits = block.modulation;
+    if !two_bit {
+        return (0..16)
+            .map(|i| {
+                let value = (bits >> (2 * i) & 3) as usize;
+                match block.alternate_mode {
+                    true => [0, 4, 4 | PUNCH_THROUGH, 8][value],
+                    false => WEIGHTS[value],
+                }
+            })
+            .collect();
+    }
+    if !block.alternate_mode {
+        // One bit per pixel, selecting either color.
+        return (0..32).map(|i| (bits >> i & 1) as u8 * 8).collect();
+    }
+
+    // The low bits of the first and the central stored value select between
+    // interpolation modes, both values are stored with their high bit only.
+    if bits & 1 != 0 {
+        bits = (bits & !(1 << 20)) | (bits >> 21 & 1) << 20;
+    }
+    bits = (bits & !1) | (bits >> 1 & 1);
+    let mut weights = vec![INTERPOLATED; 32];
+    for (i, weight) in weights.iter_mut().enumerate() {
+        let (x, y) = (i % 8, i / 8);
+        if (x ^ y) & 1 == 0 {
+            *weight = WEIGHTS[(bits & 3) as usize];
+            bits >>= 2;
+        }
+    }
+    weights
+}
+
+/// How a pixel missing from a block in the 2bpp interpolated mode is computed.
+#[derive(Clone, Copy)]
+enum Interpolation {
+    Both,
+    Horizontal,
+    Vertical,
+}
+
+impl Interpolation {
+    fn of(block: &Block) -> Interpolation {
+        match (block.modulation & 1 != 0, block.modulation >> 20 & 1 != 0) {
+            (false, _) => Interpolation::Both,
+            (true, false) => Interpolation::Horizontal,
+            (true, true) => Interpolation::Vertical,
+        }
+    }
+}
+
+/// Decodes PVRTC pixel data of a `width` by `height` image to RGBA, appending it
+/// to `out`; `two_bit` selects the 2bpp variant. After each row `rows_done` is
+/// called with the number of rows decoded so far.
+///
+/// Returns false without decoding if the image isn't a power of two blocks wide
+/// and high, which Morton order requires.
+pub fn decode(
+    pixels: &[u8],
+    width: usize,
+    height: usize,
+    two_bit: bool,
+    out: &mut Vec<u8>,
+    mut rows_done: impl FnMut(usize),
+) -> bool {
+    let (block_width, block_height) = if two_bit { (8, 4) } else { (4, 4) };
+    let blocks_wide = width.div_ceil(block_width).max(2);
+    let blocks_high = height.div_ceil(block_height).max(2);
+    if !blocks_wide.is_power_of_two() || !blocks_high.is_power_of_two() {
+        return false;
+    }
+
+    let min_dimension = blocks_wide.min(blocks_high);
+    let blocks = (0..blocks_wide * blocks_high)
+        .map(|i| {
+            let offset = morton_index(i % blocks_wide, i / blocks_wide, min_dimension) * 8;
+            Block::parse(&pixels[offset..offset + 8])
+        })
+        .collect::<Vec<_>>();
+    let block_at = |x: isize, y: isize| {
+        let x = x.rem_euclid(blocks_wide as isize) as usize;
+        let y = y.rem_euclid(blocks_high as isize) as usize;
+        &blocks[y * blocks_wide + x]
+    };
+
+    // Modulation weights of the whole image, which wraps around at the edges.
+    let (full_width, full_height) = (blocks_wide * block_width, blocks_high * block_height);
+    let mut weights = vec![0; full_width * full_height];
+    for (i, block) in blocks.iter().enumerate() {
+        let (block_x, block_y) = (
+            i % blocks_wide * block_width,
+            i / blocks_wide * block_height,
+        );
+        for (j, weight) in block_weights(block, two_bit).into_iter().enumerate() {
+            let (x, y) = (block_x + j % block_width, block_y + j / block_width);
+            weights[y * full_width + x] = weight;
+        }
+    }
+    let weight_at = |x: usize, y: usize, dx: isize, dy: isize| {
+        let x = (x as isize + dx).rem_euclid(full_width as isize) as usize;
+        let y = (y as isize + dy).rem_euclid(full_height as isize) as usize;
+        weights[y * full_width + x] as u32
+    };
+
+    // Scale by which the colors of the four nearest blocks are weighted.
+    let scale_bits = (block_width * block_height).trailing_zeros();
+    out.reserve(width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            // The colors of a block are centered on it, so find the blocks whose
+            // centers surround the pixel.
+            let px = x as isize - block_width as isize / 2;
+            let py = y as isize - block_height as isize / 2;
+            let (bx, by) = (
+                px.div_euclid(block_width as isize),
+                py.div_euclid(block_height as isize),
+            );
+            let fx = px.rem_euclid(block_width as isize) as u32;
+            let fy = py.rem_euclid(block_height as isize) as u32;
+            let (w, h) = (block_width as u32, block_height as u32);
+            let neighbours = [
+                (block_at(bx, by), (w - fx) * (h - fy)),
+                (block_at(bx + 1, by), fx * (h - fy)),
+                (block_at(bx, by + 1), (w - fx) * fy),
+                (block_at(bx + 1, by + 1), fx * fy),
+            ];
+            let upscale = |color: fn(&Block) -> Color| -> [u32; 4] {
+                let sum = |c: usize| {
+                    neighbours
+                        .iter()
+                        .map(|(block, weight)| color(block)[c] * weight)
+                        .sum::<u32>()
+                };
+                let channel = |c: usize| {
+                    let sum = sum(c);
+                    (sum >> (scale_bits - 3)) + (sum >> (scale_bits + 2))
+                };
+                let alpha = sum(3);
+                [
+                    channel(0),
+                    channel(1),
+                    channel(2),
+                    (alpha << 4 >> scale_bits) + (alpha >> scale_bits),
+                ]
+            };
+            let a = upscale(|block| block.a);
+            let b = upscale(|block| block.b);
+
+            let mut weight = weights[y * full_width + x] as u32;
+            if weight == INTERPOLATED as u32 {
+                let horizontal = weight_at(x, y, -1, 0) + weight_at(x, y, 1, 0);
+                let vertical = weight_at(x, y, 0, -1) + weight_at(x, y, 0, 1);
+                let block = &blocks[y / block_height * blocks_wide + x / block_width];
+                weight = match Interpolation::of(block) {
+                    Interpolation::Both => (horizontal + vertical + 2) / 4,
+                    Interpolation::Horizontal => horizontal.div_ceil(2),
+                    Interpolation::Vertical => vertical.div_ceil(2),
+                };
+            }
+            let punch_through = weight & PUNCH_THROUGH as u32 != 0;
+            let weight = weight & !(PUNCH_THROUGH as u32);
+
+            let blend = |c: usize| ((a[c] * (8 - weight) + b[c] * weight) / 8) as u8;
+            let alpha = if punch_through { 0 } else { blend(3) };
+            out.extend_from_slice(&[blend(0), blend(1), blend(2), alpha]);
+        }
+        rows_done(y + 1);
+    }
+    true
+}
@@ -0,0 +1,209 @@
+use std::{f32::consts::PI, str::FromStr};
+
+/// Resampling filter used when resizing images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Filter {
+    Nearest,
+    /// Linear interpolation
+    #[value(alias = "bilinear")]
+    Triangle,
+    /// Windowed sinc filter, sharpest but slowest
+    Lanczos3,
+}
+
+impl Filter {
+    /// Radius of the filter kernel in source pixels when upscaling.
+    fn support(self) -> f32 {
+        match self {
+            Filter::Nearest => 0.5,
+            Filter::Triangle => 1.0,
+            Filter::Lanczos3 => 3.0,
+        }
+    }
+
+    fn kernel(self, x: f32) -> f32 {
+        let sinc = |x: f32| {
+            if x == 0.0 {
+                1.0
+            } else {
+                (PI * x).sin() / (PI * x)
+            }
+        };
+
+        let x = x.abs();
+        match self {
+            Filter::Nearest => (x < 0.5) as u8 as f32,
+            Filter::Triangle => (1.0 - x).max(0.0),
+            Filter::Lanczos3 if x < 3.0 => sinc(x) * sinc(x / 3.0),
+            Filter::Lanczos3 => 0.0,
+        }
+    }
+}
+
+/// Target dimensions of a resize, parsed from `<width>x<height>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Size {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Size {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((width, height)) = s.split_once('x') else {
+            return Err("expected <width>x<height>".to_string());
+        };
+
+        let parse = |value: &str| match value.parse::<u32>() {
+            Ok(0) => Err("dimensions must not be zero".to_string()),
+            Ok(value) => Ok(value),
+            Err(e) => Err(format!("{value:?}: {e}")),
+        };
+        Ok(Size {
+            width: parse(width)?,
+            height: parse(height)?,
+        })
+    }
+}
+
+/// Requested dimensions of a resize, parsed from `<width>x<height>` where either
+/// side may be left out to keep the aspect ratio, e.g. `256x` or `x256`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Target {
+    width: Option<u32>,
+    height: Option<u32>,
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((width, height)) = s.split_once('x') else {
+            return Err("expected <width>x<height>, <width>x or x<height>".to_string());
+        };
+
+        let parse = |value: &str| match value.parse::<u32>() {
+            _ if value.is_empty() => Ok(None),
+            Ok(0) => Err("dimensions must not be zero".to_string()),
+            Ok(value) => Ok(Some(value)),
+            Err(e) => Err(format!("{value:?}: {e}")),
+        };
+        let target = Target {
+            width: parse(width)?,
+            height: parse(height)?,
+        };
+        if target.width.is_none() && target.height.is_none() {
+            return Err("at least one dimension is required".to_string());
+        }
+        Ok(target)
+    }
+}
+
+impl Target {
+    /// Dimensions an image of `width` by `height` gets resized to, a missing side
+    /// is derived from the aspect ratio and rounded to at least one pixel.
+    pub fn resolve(self, width: u32, height: u32) -> Size {
+        let scaled = |value: u32, numerator: u32, denominator: u32| {
+            ((value as f64 * numerator as f64 / denominator as f64).round() as u32).max(1)
+        };
+        match (self.width, self.height) {
+            (Some(width), Some(height)) => Size { width, height },
+            (Some(new_width), None) => Size {
+                width: new_width,
+                height: scaled(height, new_width, width),
+            },
+            (None, Some(new_height)) => Size {
+                width: scaled(width, new_height, height),
+                height: new_height,
+            },
+            (None, None) => unreachable!("targets have at least one dimension"),
+        }
+    }
+}
+
+/// Contribution of a range of source pixels to a single destination pixel.
+struct Contribution {
+    start: usize,
+    weights: Vec<f32>,
+}
+
+fn contributions(src: usize, dst: usize, filter: Filter) -> Vec<Contribution> {
+    let ratio = src as f32 / dst as f32;
+    // When downscaling the kernel is stretched so that every source pixel contributes.
+    let scale = ratio.max(1.0);
+    let support = filter.support() * scale;
+
+    (0..dst)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * ratio;
+            if filter == Filter::Nearest {
+                return Contribution {
+                    start: (center as usize).min(src - 1),
+                    weights: vec![1.0],
+                };
+            }
+
+            let start = (center - support).floor().max(0.0) as usize;
+            let end = ((center + support).ceil() as usize).clamp(start + 1, src);
+            let mut weights = (start..end)
+                .map(|j| filter.kernel((j as f32 + 0.5 - center) / scale))
+                .collect::<Vec<_>>();
+
+            let sum = weights.iter().sum::<f32>();
+            if sum != 0.0 {
+                weights.iter_mut().for_each(|weight| *weight /= sum);
+            }
+            Contribution { start, weights }
+        })
+        .collect()
+}
+
+/// Resizes an image with `channels` bytes per pixel by resampling it first
+/// horizontally and then vertically.
+pub fn resize(
+    buffer: &[u8],
+    (width, height): (u32, u32),
+    channels: usize,
+    size: Size,
+    filter: Filter,
+) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let (new_width, new_height) = (size.width as usize, size.height as usize);
+
+    let horizontal = contributions(width, new_width, filter);
+    let mut intermediate = vec![0.0f32; new_width * height * channels];
+    for y in 0..height {
+        for (x, contribution) in horizontal.iter().enumerate() {
+            let out = &mut intermediate[(y * new_width + x) * channels..][..channels];
+            for (i, weight) in contribution.weights.iter().enumerate() {
+                let pixel = &buffer[(y * width + contribution.start + i) * channels..][..channels];
+                for (out, &value) in out.iter_mut().zip(pixel) {
+                    *out += value as f32 * weight;
+                }
+            }
+        }
+    }
+
+    let vertical = contributions(height, new_height, filter);
+    let mut result = vec![0; new_width * new_height * channels];
+    for (y, contribution) in vertical.iter().enumerate() {
+        for x in 0..new_width {
+            let mut sums = vec![0.0f32; channels];
+            for (i, weight) in contribution.weights.iter().enumerate() {
+                let pixel = &intermediate[((contribution.start + i) * new_width + x) * channels..]
+                    [..channels];
+                for (sum, &value) in sums.iter_mut().zip(pixel) {
+                    *sum += value * weight;
+                }
+            }
+
+            let out = &mut result[(y * new_width + x) * channels..][..channels];
+            for (out, sum) in out.iter_mut().zip(sums) {
+                *out = sum.round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+
+    result
+}
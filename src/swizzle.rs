@@ -0,0 +1,41 @@
+//! Fixed permutations of the channels of 4-byte pixels, applied in place.
+//!
+//! Each function is named after the order of the bytes it reads and the one it
+//! writes. A trailing partial pixel is left untouched. Permutations chosen at
+//! runtime with `tex2png --swizzle` are handled separately.
+
+/// Moves byte `order[i]` of every pixel to byte `i`.
+fn permute(pixels: &mut [u8], order: [usize; 4]) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        let source: [u8; 4] = (&*pixel).try_into().unwrap();
+        pixel.copy_from_slice(&order.map(|index| source[index]));
+    }
+}
+
+/// Leaves the pixels as they are, for formats already stored as RGBA.
+pub fn identity(_pixels: &mut [u8]) {}
+
+/// Swaps the red and blue channels of BGRA pixels.
+pub fn bgra_to_rgba(pixels: &mut [u8]) {
+    permute(pixels, [2, 1, 0, 3]);
+}
+
+/// Swaps the red and blue channels of RGBA pixels, the inverse of [`bgra_to_rgba`].
+pub fn rgba_to_bgra(pixels: &mut [u8]) {
+    bgra_to_rgba(pixels);
+}
+
+/// Moves the leading alpha of ARGB pixels to the end.
+pub fn argb_to_rgba(pixels: &mut [u8]) {
+    permute(pixels, [1, 2, 3, 0]);
+}
+
+/// Moves the trailing alpha of RGBA pixels to the start, the inverse of [`argb_to_rgba`].
+pub fn rgba_to_argb(pixels: &mut [u8]) {
+    permute(pixels, [3, 0, 1, 2]);
+}
+
+/// Reverses the channels of ABGR pixels.
+pub fn abgr_to_rgba(pixels: &mut [u8]) {
+    permute(pixels, [3, 2, 1, 0]);
+}
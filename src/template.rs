@@ -0,0 +1,123 @@
+use std::str::FromStr;
+
+use crate::TexFormat;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholder {
+    Stem,
+    Ext,
+    Width,
+    Height,
+    Format,
+    Index,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+/// Output file name with `{placeholder}` fields replaced for every converted texture.
+///
+/// Literal braces are written as `{{` and `}}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputTemplate(Vec<Segment>);
+
+/// Values substituted into an [`OutputTemplate`].
+pub struct TemplateFields<'a> {
+    /// File name of the input without its extension
+    pub stem: &'a str,
+    /// Extension of the input file
+    pub ext: &'a str,
+    pub width: u32,
+    pub height: u32,
+    pub format: TexFormat,
+    /// Position of the texture in a file holding several concatenated ones, 0 otherwise
+    pub index: usize,
+}
+
+impl FromStr for OutputTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let Some((name, rest)) = chars.as_str().split_once('}') else {
+                        return Err("unterminated placeholder".to_string());
+                    };
+                    let placeholder = match name {
+                        "stem" => Placeholder::Stem,
+                        "ext" => Placeholder::Ext,
+                        "width" => Placeholder::Width,
+                        "height" => Placeholder::Height,
+                        "format" => Placeholder::Format,
+                        "index" => Placeholder::Index,
+                        _ => {
+                            return Err(format!(
+                                "unknown placeholder {{{name}}}, expected one of {{stem}}, \
+                                 {{ext}}, {{width}}, {{height}}, {{format}} or {{index}}"
+                            ))
+                        }
+                    };
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Placeholder(placeholder));
+                    chars = rest.chars();
+                }
+                '}' => return Err("unmatched '}', write '}}' for a literal brace".to_string()),
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        if segments.is_empty() {
+            return Err("template must not be empty".to_string());
+        }
+        Ok(OutputTemplate(segments))
+    }
+}
+
+impl OutputTemplate {
+    /// Whether the template tells the textures of a file apart with `{index}`.
+    pub fn uses_index(&self) -> bool {
+        self.0.contains(&Segment::Placeholder(Placeholder::Index))
+    }
+
+    pub fn expand(&self, fields: &TemplateFields) -> String {
+        let mut result = String::new();
+        for segment in &self.0 {
+            match segment {
+                Segment::Literal(literal) => result.push_str(literal),
+                Segment::Placeholder(Placeholder::Stem) => result.push_str(fields.stem),
+                Segment::Placeholder(Placeholder::Ext) => result.push_str(fields.ext),
+                Segment::Placeholder(Placeholder::Width) => {
+                    result.push_str(&fields.width.to_string())
+                }
+                Segment::Placeholder(Placeholder::Height) => {
+                    result.push_str(&fields.height.to_string())
+                }
+                Segment::Placeholder(Placeholder::Format) => result.push_str(fields.format.name()),
+                Segment::Placeholder(Placeholder::Index) => {
+                    result.push_str(&fields.index.to_string())
+                }
+            }
+        }
+        result
+    }
+}
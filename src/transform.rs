@@ -0,0 +1,505 @@
+use std::{collections::VecDeque, str::FromStr};
+
+/// A rectangular region of an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Rect {
+    type Err = String;
+
+    /// Parses a rectangle in the `<x>,<y>,<width>,<height>` form.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|value| {
+                value
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|e| format!("{value:?}: {e}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let [x, y, width, height] = values[..] else {
+            return Err("expected <x>,<y>,<width>,<height>".to_string());
+        };
+        if width == 0 || height == 0 {
+            return Err("rectangle must not be empty".to_string());
+        }
+
+        Ok(Rect {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+}
+
+impl Rect {
+    /// Whether the rectangle lies entirely within a `width` by `height` image.
+    pub fn fits_within(self, width: u32, height: u32) -> bool {
+        self.x
+            .checked_add(self.width)
+            .is_some_and(|end| end <= width)
+            && self
+                .y
+                .checked_add(self.height)
+                .is_some_and(|end| end <= height)
+    }
+}
+
+/// Layout of equally sized cells in a sprite sheet, parsed from `<columns>x<rows>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Grid {
+    pub columns: u32,
+    pub rows: u32,
+}
+
+impl FromStr for Grid {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((columns, rows)) = s.split_once('x') else {
+            return Err("expected <columns>x<rows>".to_string());
+        };
+
+        let parse = |value: &str| match value.parse::<u32>() {
+            Ok(0) => Err("grid must have at least one column and row".to_string()),
+            Ok(value) => Ok(value),
+            Err(e) => Err(format!("{value:?}: {e}")),
+        };
+        Ok(Grid {
+            columns: parse(columns)?,
+            rows: parse(rows)?,
+        })
+    }
+}
+
+impl Grid {
+    /// Rectangles of every cell of a `width` by `height` image in row-major order.
+    ///
+    /// Pixels beyond the last full column or row are not part of any cell.
+    pub fn cells(self, width: u32, height: u32) -> Vec<Rect> {
+        self.cells_of_size(width / self.columns, height / self.rows)
+    }
+
+    /// Rectangles of every cell in row-major order when cells are `cell_width`
+    /// by `cell_height` pixels large.
+    pub fn cells_of_size(self, cell_width: u32, cell_height: u32) -> Vec<Rect> {
+        (0..self.rows)
+            .flat_map(|row| {
+                (0..self.columns).map(move |column| Rect {
+                    x: column * cell_width,
+                    y: row * cell_height,
+                    width: cell_width,
+                    height: cell_height,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Finds the smallest rectangle containing every pixel with a non-zero alpha.
+///
+/// Pixels are `channels` bytes long with the alpha value stored last, returns
+/// `None` if the whole image is transparent.
+pub fn opaque_bounds(buffer: &[u8], width: u32, channels: usize) -> Option<Rect> {
+    let stride = width as usize * channels;
+    if stride == 0 {
+        return None;
+    }
+
+    let (mut min_x, mut min_y) = (u32::MAX, u32::MAX);
+    let (mut max_x, mut max_y) = (0, 0);
+    for (y, row) in buffer.chunks_exact(stride).enumerate() {
+        for (x, pixel) in row.chunks_exact(channels).enumerate() {
+            if pixel[channels - 1] != 0 {
+                min_x = min_x.min(x as u32);
+                min_y = min_y.min(y as u32);
+                max_x = max_x.max(x as u32);
+                max_y = max_y.max(y as u32);
+            }
+        }
+    }
+
+    (min_x != u32::MAX).then(|| Rect {
+        x: min_x,
+        y: min_y,
+        width: max_x - min_x + 1,
+        height: max_y - min_y + 1,
+    })
+}
+
+/// Copies the `rect` region out of an image that is `width` pixels wide.
+pub fn crop(buffer: &[u8], width: u32, channels: usize, rect: Rect) -> Vec<u8> {
+    let stride = width as usize * channels;
+    let row_size = rect.width as usize * channels;
+    let mut result = Vec::with_capacity(row_size * rect.height as usize);
+    for y in rect.y..rect.y + rect.height {
+        let start = y as usize * stride + rect.x as usize * channels;
+        result.extend_from_slice(&buffer[start..start + row_size]);
+    }
+    result
+}
+
+/// Centers an image with `channels` byte pixels on a transparent square as large
+/// as its longer side, returning the square's pixels and side length.
+#[cfg(feature = "image-formats")]
+pub fn pad_to_square(
+    buffer: &[u8],
+    (width, height): (u32, u32),
+    channels: usize,
+) -> (Vec<u8>, u32) {
+    let side = width.max(height);
+    if width == height {
+        return (buffer.to_vec(), side);
+    }
+
+    let canvas = Rect {
+        x: (side - width) / 2,
+        y: (side - height) / 2,
+        width: side,
+        height: side,
+    };
+    (pad(buffer, width, channels, canvas), side)
+}
+
+/// Places an image that is `width` pixels wide at `canvas.x`, `canvas.y` on a
+/// transparent canvas of `canvas.width` by `canvas.height`, which it has to fit on.
+pub fn pad(buffer: &[u8], width: u32, channels: usize, canvas: Rect) -> Vec<u8> {
+    let stride = canvas.width as usize * channels;
+    let row_size = width as usize * channels;
+    let mut result = vec![0; stride * canvas.height as usize];
+    if row_size == 0 {
+        return result;
+    }
+    for (y, row) in buffer.chunks_exact(row_size).enumerate() {
+        let start = (canvas.y as usize + y) * stride + canvas.x as usize * channels;
+        result[start..start + row_size].copy_from_slice(row);
+    }
+    result
+}
+
+/// Clockwise rotation applied by `tex2png --rotate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Rotation {
+    #[value(name = "90")]
+    Quarter,
+    #[value(name = "180")]
+    Half,
+    #[value(name = "270")]
+    ThreeQuarters,
+}
+
+/// Rotates a `width` by `height` image with `channels` byte pixels clockwise,
+/// returning the rotated pixels and their dimensions.
+pub fn rotate(
+    buffer: &[u8],
+    (width, height): (u32, u32),
+    channels: usize,
+    rotation: Rotation,
+) -> (Vec<u8>, (u32, u32)) {
+    let (w, h) = (width as usize, height as usize);
+    let (rotated_width, rotated_height) = match rotation {
+        Rotation::Half => (w, h),
+        Rotation::Quarter | Rotation::ThreeQuarters => (h, w),
+    };
+
+    let mut result = Vec::with_capacity(buffer.len());
+    for y in 0..rotated_height {
+        for x in 0..rotated_width {
+            let (source_x, source_y) = match rotation {
+                Rotation::Quarter => (y, h - 1 - x),
+                Rotation::Half => (w - 1 - x, h - 1 - y),
+                Rotation::ThreeQuarters => (w - 1 - y, x),
+            };
+            let start = (source_y * w + source_x) * channels;
+            result.extend_from_slice(&buffer[start..start + channels]);
+        }
+    }
+    (result, (rotated_width as u32, rotated_height as u32))
+}
+
+/// Source of a single output channel in a [`Swizzle`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwizzleComponent {
+    /// Index of an RGBA input channel.
+    Channel(usize),
+    Zero,
+    One,
+}
+
+/// A permutation of RGBA channels, parsed from patterns like `argb` or `rgb1`.
+///
+/// Each character selects what gets stored in the respective output channel:
+/// an input channel (`r`, `g`, `b` or `a`), or the constants `0` (0x00) and `1` (0xFF).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Swizzle(pub [SwizzleComponent; 4]);
+
+impl FromStr for Swizzle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let components = s
+            .chars()
+            .map(|c| {
+                Ok(match c.to_ascii_lowercase() {
+                    'r' => SwizzleComponent::Channel(0),
+                    'g' => SwizzleComponent::Channel(1),
+                    'b' => SwizzleComponent::Channel(2),
+                    'a' => SwizzleComponent::Channel(3),
+                    '0' => SwizzleComponent::Zero,
+                    '1' => SwizzleComponent::One,
+                    _ => return Err(format!("invalid swizzle component '{c}'")),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Swizzle(components.try_into().map_err(|_| {
+            "swizzle pattern must have exactly four components".to_string()
+        })?))
+    }
+}
+
+impl Swizzle {
+    /// Applies the permutation to every pixel of an RGBA buffer.
+    pub fn apply(self, buffer: &[u8]) -> Vec<u8> {
+        let mut result = Vec::with_capacity(buffer.len());
+        for pixel in buffer.chunks_exact(4) {
+            result.extend(self.0.map(|component| match component {
+                SwizzleComponent::Channel(index) => pixel[index],
+                SwizzleComponent::Zero => 0x00,
+                SwizzleComponent::One => 0xFF,
+            }));
+        }
+        result
+    }
+
+    /// Like [`Swizzle::apply`], but for RGBA buffers with big-endian 16-bit samples.
+    pub fn apply_16(self, buffer: &[u8]) -> Vec<u8> {
+        let mut result = Vec::with_capacity(buffer.len());
+        for pixel in buffer.chunks_exact(8) {
+            let channels: [u16; 4] =
+                std::array::from_fn(|i| u16::from_be_bytes([pixel[2 * i], pixel[2 * i + 1]]));
+            for component in self.0 {
+                let value = match component {
+                    SwizzleComponent::Channel(index) => channels[index],
+                    SwizzleComponent::Zero => 0x0000,
+                    SwizzleComponent::One => 0xFFFF,
+                };
+                result.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+        result
+    }
+}
+
+/// An opaque color parsed from `RRGGBB` hex notation, optionally prefixed with `#`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb(pub [u8; 3]);
+
+impl FromStr for Rgb {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        if hex.len() != 6 || !hex.is_ascii() {
+            return Err("expected a color in RRGGBB form".to_string());
+        }
+
+        let mut color = [0; 3];
+        for (i, channel) in color.iter_mut().enumerate() {
+            *channel = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+                .map_err(|_| format!("invalid hex color {s:?}"))?;
+        }
+        Ok(Rgb(color))
+    }
+}
+
+/// Makes every RGBA pixel whose color channels are all within `tolerance` of
+/// `key` fully transparent.
+pub fn apply_color_key(buffer: &mut [u8], key: Rgb, tolerance: u8) {
+    for pixel in buffer.chunks_exact_mut(4) {
+        if pixel[..3]
+            .iter()
+            .zip(key.0)
+            .all(|(&channel, key)| channel.abs_diff(key) <= tolerance)
+        {
+            pixel[3] = 0;
+        }
+    }
+}
+
+/// Makes the last channel of every pixel, its alpha, fully opaque if it's at
+/// least `threshold` and fully transparent otherwise.
+pub fn threshold_alpha(buffer: &mut [u8], channels: usize, threshold: u8) {
+    for pixel in buffer.chunks_exact_mut(channels) {
+        let alpha = &mut pixel[channels - 1];
+        *alpha = if *alpha >= threshold { 0xFF } else { 0x00 };
+    }
+}
+
+/// Fills the color of every fully transparent pixel of an RGBA image that is
+/// `width` pixels wide with the color of the nearest visible pixel, leaving its
+/// alpha at zero.
+///
+/// This keeps filtering from pulling in the arbitrary colors of transparent pixels
+/// around the edges of visible ones. Distances are counted in steps between
+/// horizontally or vertically adjacent pixels, images without any visible pixel
+/// are left as they are.
+pub fn bleed(buffer: &mut [u8], width: u32) {
+    let width = width as usize;
+    let pixel_count = buffer.len() / 4;
+    let mut filled = buffer
+        .chunks_exact(4)
+        .map(|pixel| pixel[3] != 0)
+        .collect::<Vec<_>>();
+    let mut queue = (0..pixel_count)
+        .filter(|&i| filled[i])
+        .collect::<VecDeque<_>>();
+
+    while let Some(i) = queue.pop_front() {
+        let (x, y) = (i % width, i / width);
+        let neighbours = [
+            (x > 0).then(|| i - 1),
+            (x + 1 < width).then(|| i + 1),
+            (y > 0).then(|| i - width),
+            (i + width < pixel_count).then(|| i + width),
+        ];
+        for j in neighbours.into_iter().flatten() {
+            if !filled[j] {
+                filled[j] = true;
+                buffer.copy_within(i * 4..i * 4 + 3, j * 4);
+                queue.push_back(j);
+            }
+        }
+    }
+}
+
+/// Converts premultiplied RGBA pixels to straight alpha in place.
+///
+/// Fully transparent pixels carry no color information and are left as they are.
+pub fn unpremultiply(buffer: &mut [u8]) {
+    for pixel in buffer.chunks_exact_mut(4) {
+        let alpha = pixel[3] as u32;
+        if alpha == 0 {
+            continue;
+        }
+        for channel in &mut pixel[..3] {
+            *channel = ((*channel as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+        }
+    }
+}
+
+/// Whether RGBA pixels look like their alpha is premultiplied, which is the case
+/// when the color of translucent pixels hardly ever exceeds their alpha.
+///
+/// Straight alpha images with very few translucent pixels or only dark ones can't
+/// be told apart and are reported as not premultiplied.
+pub fn looks_premultiplied(buffer: &[u8]) -> bool {
+    // Fewer translucent pixels than this with some color say too little.
+    const MIN_SAMPLES: usize = 16;
+    // Lossy formats can push a channel slightly above alpha, so tolerate a few.
+    const MAX_EXCEEDING_PERCENT: usize = 1;
+
+    let (mut samples, mut exceeding) = (0, 0);
+    for pixel in buffer.chunks_exact(4) {
+        let alpha = pixel[3];
+        let brightest = pixel[0].max(pixel[1]).max(pixel[2]);
+        if alpha == 0xFF || brightest == 0 {
+            continue;
+        }
+        samples += 1;
+        exceeding += (brightest > alpha) as usize;
+    }
+    samples >= MIN_SAMPLES && exceeding * 100 <= samples * MAX_EXCEEDING_PERCENT
+}
+
+/// The alpha of every RGBA pixel as a grayscale pixel.
+pub fn alpha_to_gray(buffer: &[u8]) -> Vec<u8> {
+    buffer.chunks_exact(4).map(|pixel| pixel[3]).collect()
+}
+
+/// Blends straight alpha RGBA pixels over an opaque `background`, returning RGB pixels.
+pub fn composite_over(buffer: &[u8], background: Rgb) -> Vec<u8> {
+    buffer
+        .chunks_exact(4)
+        .flat_map(|pixel| {
+            let alpha = pixel[3] as u32;
+            std::array::from_fn::<u8, 3, _>(|c| {
+                ((pixel[c] as u32 * alpha + background.0[c] as u32 * (255 - alpha) + 127) / 255)
+                    as u8
+            })
+        })
+        .collect()
+}
+
+/// Multiplies the color of straight alpha RGBA pixels by their alpha in place.
+///
+/// Both this and [`unpremultiply`] round to nearest, which makes premultiplying
+/// an unpremultiplied pixel give back exactly the original value.
+pub fn premultiply(buffer: &mut [u8]) {
+    for pixel in buffer.chunks_exact_mut(4) {
+        let alpha = pixel[3] as u32;
+        for channel in &mut pixel[..3] {
+            *channel = ((*channel as u32 * alpha + 127) / 255) as u8;
+        }
+    }
+}
+
+/// Transfer function to encode the decoded colors with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gamma {
+    /// Encode linear values with the sRGB transfer function.
+    Srgb,
+    /// Decode sRGB values to linear light.
+    Linear,
+    /// Encode linear values with a plain power curve of the given gamma.
+    Power(f64),
+}
+
+impl FromStr for Gamma {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "srgb" => Ok(Gamma::Srgb),
+            "linear" => Ok(Gamma::Linear),
+            value => match value.parse::<f64>() {
+                Ok(gamma) if gamma > 0.0 && gamma.is_finite() => Ok(Gamma::Power(gamma)),
+                _ => Err("expected srgb, linear or a positive gamma value".to_string()),
+            },
+        }
+    }
+}
+
+impl Gamma {
+    fn transfer(self, value: f64) -> f64 {
+        match self {
+            Gamma::Srgb if value <= 0.0031308 => value * 12.92,
+            Gamma::Srgb => 1.055 * value.powf(1.0 / 2.4) - 0.055,
+            Gamma::Linear if value <= 0.04045 => value / 12.92,
+            Gamma::Linear => ((value + 0.055) / 1.055).powf(2.4),
+            Gamma::Power(gamma) => value.powf(1.0 / gamma),
+        }
+    }
+
+    /// Applies the transfer function to the color channels of every pixel,
+    /// which are the first `color_channels` of each `channels` bytes.
+    pub fn apply(self, buffer: &mut [u8], channels: usize, color_channels: usize) {
+        let table: [u8; 256] = std::array::from_fn(|value| {
+            (self.transfer(value as f64 / 255.0) * 255.0).round() as u8
+        });
+
+        for pixel in buffer.chunks_exact_mut(channels) {
+            for channel in &mut pixel[..color_channels] {
+                *channel = table[*channel as usize];
+            }
+        }
+    }
+}
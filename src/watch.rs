@@ -0,0 +1,108 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// How often inputs are checked for modifications.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// How often the inputs are listed again to find files added to watched
+/// directories or archives, which walks every directory.
+const RESCAN_INTERVAL: Duration = Duration::from_secs(2);
+/// How long a file has to stay unmodified before it gets reconverted, so that
+/// a file which is still being written is only converted once.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Formats the time of day of `time` in UTC as `HH:MM:SS`.
+fn format_timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600 % 24,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Converts every job in `jobs` and then keeps reconverting inputs whenever
+/// their modification time changes.
+///
+/// `rescan` is called every [`RESCAN_INTERVAL`] so new files in watched
+/// directories are picked up. What it prints, like warnings about skipped files,
+/// is only shown when the jobs change or it fails differently than before, and
+/// inputs that can't be listed any more keep their previous jobs until they can.
+/// `convert` is expected to report its own errors.
+pub fn watch(
+    mut jobs: Vec<(PathBuf, PathBuf)>,
+    mut rescan: impl FnMut() -> Result<Vec<(PathBuf, PathBuf)>, ExitCode>,
+    mut convert: impl FnMut(&Path, &Path) -> bool,
+) -> ! {
+    let mut seen: HashMap<PathBuf, SystemTime> = HashMap::new();
+    let mut pending: HashMap<PathBuf, (PathBuf, Option<Instant>)> = HashMap::new();
+    let mut first = true;
+    let mut last_rescan = Instant::now();
+    let mut last_error = Vec::new();
+
+    loop {
+        if last_rescan.elapsed() >= RESCAN_INTERVAL {
+            last_rescan = Instant::now();
+            match crate::capture(&mut rescan) {
+                (Ok(rescanned), lines) => {
+                    if rescanned != jobs {
+                        crate::replay(lines);
+                        jobs = rescanned;
+                    }
+                    last_error.clear();
+                }
+                (Err(_), lines) => {
+                    if lines != last_error {
+                        crate::replay(lines.clone());
+                        last_error = lines;
+                    }
+                }
+            }
+        }
+
+        for (tex_path, out_path) in &jobs {
+            // Entries of an archive are reconverted whenever the archive changes.
+            let source = crate::zip::split_entry_path(tex_path).map_or(&**tex_path, |(a, _)| a);
+            let Ok(modified) = std::fs::metadata(source).and_then(|m| m.modified()) else {
+                continue;
+            };
+
+            if seen.insert(tex_path.clone(), modified) != Some(modified) {
+                // Files present at startup are converted right away.
+                let changed_at = (!first).then(Instant::now);
+                pending.insert(tex_path.clone(), (out_path.clone(), changed_at));
+            }
+        }
+        first = false;
+
+        let now = Instant::now();
+        let mut ready = pending
+            .iter()
+            .filter(|(_, (_, changed_at))| changed_at.is_none_or(|t| now - t >= DEBOUNCE))
+            .map(|(tex_path, _)| tex_path.clone())
+            .collect::<Vec<_>>();
+        ready.sort();
+
+        for tex_path in ready {
+            let (out_path, _) = pending.remove(&tex_path).unwrap();
+            let timestamp = format_timestamp(SystemTime::now());
+            if convert(&tex_path, &out_path) {
+                eprintln!(
+                    "[{timestamp}] Converted {} -> {}",
+                    tex_path.display(),
+                    out_path.display()
+                );
+            } else {
+                eprintln!("[{timestamp}] Failed to convert {}", tex_path.display());
+            }
+        }
+
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Component, Path, PathBuf},
+};
+
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054b50;
+const CENTRAL_DIRECTORY_HEADER: u32 = 0x02014b50;
+const LOCAL_FILE_HEADER: u32 = 0x04034b50;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+/// Most bytes allocated up front for an entry, whose recorded size can't be trusted.
+const MAX_PREALLOCATION: u64 = 64 << 20;
+
+/// Separates the archive path from the entry name in `archive.zip!entry` paths.
+const ENTRY_SEPARATOR: char = '!';
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap())
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// Whether `path` is a ZIP archive that should be converted as a whole.
+pub fn is_archive(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+        && path.is_file()
+}
+
+/// Splits a path of the form `archive.zip!path/in/archive` into the archive
+/// path and the entry name.
+pub fn split_entry_path(path: &Path) -> Option<(&Path, &str)> {
+    let path = path.to_str()?;
+    // Archive names may contain the separator themselves, so try every occurrence.
+    path.match_indices(ENTRY_SEPARATOR).find_map(|(i, _)| {
+        let archive = Path::new(&path[..i]);
+        is_archive(archive).then(|| (archive, &path[i + 1..]))
+    })
+}
+
+/// Path addressing `entry` inside of `archive`, the inverse of [`split_entry_path`].
+pub fn entry_path(archive: &Path, entry: &str) -> PathBuf {
+    let mut path = archive.as_os_str().to_owned();
+    path.push(ENTRY_SEPARATOR.to_string());
+    path.push(entry);
+    PathBuf::from(path)
+}
+
+/// Reads a file from disk, or an entry from an archive if `path` has the form
+/// accepted by [`split_entry_path`].
+pub fn read(path: &Path) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    read_into(path, &mut data)?;
+    Ok(data)
+}
+
+/// Like [`read`], but replaces the contents of `data` to reuse its allocation.
+pub fn read_into(path: &Path, data: &mut Vec<u8>) -> Result<(), String> {
+    data.clear();
+    match split_entry_path(path) {
+        Some((archive, entry)) => *data = ZipArchive::open(archive)?.read(entry)?,
+        None => {
+            File::open(path)
+                .and_then(|mut file| file.read_to_end(data))
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether an entry name is a relative path that stays inside the directory it
+/// gets extracted into.
+pub fn is_safe_entry_name(name: &str) -> bool {
+    Path::new(name)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+struct Entry {
+    name: String,
+    flags: u16,
+    method: u16,
+    crc: u32,
+    compressed_size: u64,
+    uncompressed_size: u64,
+    local_header_offset: u64,
+}
+
+/// Minimal reader for ZIP archives, supporting stored and deflated entries.
+pub struct ZipArchive {
+    file: File,
+    entries: Vec<Entry>,
+}
+
+impl ZipArchive {
+    /// Opens an archive and reads its central directory.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let mut file = File::open(path).map_err(|e| e.to_string())?;
+        let length = file.seek(SeekFrom::End(0)).map_err(|e| e.to_string())?;
+
+        // The end of central directory record is 22 bytes followed by a comment of at most 64KiB.
+        let tail_length = length.min(22 + u16::MAX as u64);
+        let mut tail = vec![0; tail_length as usize];
+        file.seek(SeekFrom::Start(length - tail_length))
+            .and_then(|_| file.read_exact(&mut tail))
+            .map_err(|e| e.to_string())?;
+        let Some(end) = (0..tail.len().saturating_sub(21))
+            .rev()
+            .find(|&i| u32_at(&tail, i) == END_OF_CENTRAL_DIRECTORY)
+        else {
+            return Err("not a zip archive".to_string());
+        };
+
+        let entry_count = u16_at(&tail, end + 10);
+        let directory_size = u32_at(&tail, end + 12);
+        let directory_offset = u32_at(&tail, end + 16);
+        if entry_count == u16::MAX || directory_offset == u32::MAX {
+            return Err("zip64 archives are not supported".to_string());
+        }
+        if directory_offset as u64 + directory_size as u64 > length {
+            return Err("central directory extends past the end of the archive".to_string());
+        }
+
+        let mut directory = vec![0; directory_size as usize];
+        file.seek(SeekFrom::Start(directory_offset as u64))
+            .and_then(|_| file.read_exact(&mut directory))
+            .map_err(|e| format!("failed to read central directory: {e}"))?;
+
+        let mut entries = Vec::with_capacity(entry_count as usize);
+        let mut offset = 0;
+        for _ in 0..entry_count {
+            if directory.len() < offset + 46
+                || u32_at(&directory, offset) != CENTRAL_DIRECTORY_HEADER
+            {
+                return Err("corrupt central directory".to_string());
+            }
+
+            let name_length = u16_at(&directory, offset + 28) as usize;
+            let extra_length = u16_at(&directory, offset + 30) as usize;
+            let comment_length = u16_at(&directory, offset + 32) as usize;
+            let Some(name) = directory.get(offset + 46..offset + 46 + name_length) else {
+                return Err("corrupt central directory".to_string());
+            };
+
+            entries.push(Entry {
+                name: String::from_utf8_lossy(name).into_owned(),
+                flags: u16_at(&directory, offset + 8),
+                method: u16_at(&directory, offset + 10),
+                crc: u32_at(&directory, offset + 16),
+                compressed_size: u32_at(&directory, offset + 20) as u64,
+                uncompressed_size: u32_at(&directory, offset + 24) as u64,
+                local_header_offset: u32_at(&directory, offset + 42) as u64,
+            });
+            offset += 46 + name_length + extra_length + comment_length;
+        }
+
+        Ok(ZipArchive { file, entries })
+    }
+
+    /// Names of all file entries, directories are skipped.
+    pub fn file_names(&self) -> impl Iterator<Item = &str> {
+        self.entries
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .filter(|name| !name.ends_with('/'))
+    }
+
+    /// Reads and decompresses the entry called `name`.
+    pub fn read(&mut self, name: &str) -> Result<Vec<u8>, String> {
+        let Some(entry) = self.entries.iter().find(|entry| entry.name == name) else {
+            return Err(format!("no entry named {name:?} in archive"));
+        };
+        if entry.flags & 1 != 0 {
+            return Err(format!("entry {name:?} is encrypted"));
+        }
+
+        let mut header = [0; 30];
+        self.file
+            .seek(SeekFrom::Start(entry.local_header_offset))
+            .and_then(|_| self.file.read_exact(&mut header))
+            .map_err(|e| e.to_string())?;
+        if u32_at(&header, 0) != LOCAL_FILE_HEADER {
+            return Err(format!("corrupt local header for entry {name:?}"));
+        }
+        let skip = u16_at(&header, 26) as i64 + u16_at(&header, 28) as i64;
+        self.file
+            .seek(SeekFrom::Current(skip))
+            .map_err(|e| e.to_string())?;
+
+        let mut compressed = (&mut self.file).take(entry.compressed_size);
+        let mut data = Vec::with_capacity(entry.uncompressed_size.min(MAX_PREALLOCATION) as usize);
+        // A byte more than recorded is enough to tell that the size is wrong.
+        let limit = entry.uncompressed_size + 1;
+        let result = match entry.method {
+            METHOD_STORED => compressed.read_to_end(&mut data),
+            METHOD_DEFLATED => flate2::read::DeflateDecoder::new(compressed)
+                .take(limit)
+                .read_to_end(&mut data),
+            method => {
+                return Err(format!(
+                    "entry {name:?} uses unsupported compression method {method}"
+                ))
+            }
+        };
+        result.map_err(|e| format!("failed to read entry {name:?}: {e}"))?;
+
+        let mut crc = flate2::Crc::new();
+        crc.update(&data);
+        if data.len() as u64 != entry.uncompressed_size || crc.sum() != entry.crc {
+            return Err(format!("entry {name:?} is corrupt: checksum mismatch"));
+        }
+
+        Ok(data)
+    }
+}
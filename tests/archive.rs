@@ -0,0 +1,33 @@
+mod common;
+
+use common::scratch_dir;
+
+#[test]
+fn forged_directory_size_is_rejected() {
+    let dir = scratch_dir("forged_directory_size_is_rejected");
+    // An end of central directory record claiming one entry in a central
+    // directory of almost 4 GiB, in an archive of only the record itself.
+    let mut archive = Vec::new();
+    archive.extend_from_slice(&0x0605_4B50u32.to_le_bytes());
+    archive.extend_from_slice(&[0; 6]);
+    archive.extend_from_slice(&1u16.to_le_bytes());
+    archive.extend_from_slice(&0xFFFF_FFF0u32.to_le_bytes());
+    archive.extend_from_slice(&0u32.to_le_bytes());
+    archive.extend_from_slice(&0u16.to_le_bytes());
+    let archive_path = dir.join("forged.zip");
+    std::fs::write(&archive_path, archive).unwrap();
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_siltex"))
+        .arg("tex2png")
+        .arg(&archive_path)
+        .arg("-o")
+        .arg(dir.join("out"))
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(3), "{stderr}");
+    assert!(
+        stderr.contains("central directory extends past the end"),
+        "{stderr}"
+    );
+}
@@ -0,0 +1,166 @@
+//! Extracting the sprites of atlas descriptors with `tex2png --atlas`.
+
+mod common;
+
+use common::{fixture, read_png, scratch_dir, siltex};
+
+const PLIST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+    <dict>
+        <key>frames</key>
+        <dict>
+            <key>plain.png</key>
+            <dict>
+                <key>frame</key>
+                <string>{{0,0},{4,2}}</string>
+                <key>offset</key>
+                <string>{0,0}</string>
+                <key>rotated</key>
+                <false/>
+                <key>sourceColorRect</key>
+                <string>{{0,0},{4,2}}</string>
+                <key>sourceSize</key>
+                <string>{4,2}</string>
+            </dict>
+            <key>turned.png</key>
+            <dict>
+                <key>frame</key>
+                <string>{{2,1},{3,5}}</string>
+                <key>rotated</key>
+                <true/>
+                <key>sourceColorRect</key>
+                <string>{{0,0},{3,5}}</string>
+                <key>sourceSize</key>
+                <string>{3,5}</string>
+            </dict>
+            <key>trimmed.png</key>
+            <dict>
+                <key>frame</key>
+                <string>{{4,4},{2,3}}</string>
+                <key>rotated</key>
+                <false/>
+                <key>sourceColorRect</key>
+                <string>{{1,2},{2,3}}</string>
+                <key>sourceSize</key>
+                <string>{5,6}</string>
+            </dict>
+            <key>centered.png</key>
+            <dict>
+                <key>textureRect</key>
+                <string>{{0,4},{2,2}}</string>
+                <key>textureRotated</key>
+                <false/>
+                <key>spriteOffset</key>
+                <string>{1,-1}</string>
+                <key>spriteSourceSize</key>
+                <string>{6,4}</string>
+            </dict>
+        </dict>
+        <key>metadata</key>
+        <dict>
+            <key>format</key>
+            <integer>2</integer>
+        </dict>
+    </dict>
+</plist>
+"#;
+
+/// Builds the expected `width` by `height` sprite from the pixel each position takes,
+/// `None` for transparent padding.
+fn expected_sprite(
+    width: u32,
+    height: u32,
+    pixel: impl Fn(u32, u32) -> Option<[u8; 4]>,
+) -> (u32, u32, Vec<u8>) {
+    let pixels = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .flat_map(|(x, y)| pixel(x, y).unwrap_or([0; 4]))
+        .collect();
+    (width, height, pixels)
+}
+
+#[test]
+fn plist_frames_are_rotated_and_untrimmed() {
+    let dir = scratch_dir("plist_frames_are_rotated_and_untrimmed");
+    let plist_path = dir.join("atlas.plist");
+    std::fs::write(&plist_path, PLIST).unwrap();
+    siltex(&[
+        "tex2png".as_ref(),
+        fixture("bgra8888.tex").as_os_str(),
+        "-o".as_ref(),
+        dir.join("atlas.png").as_os_str(),
+        "--atlas".as_ref(),
+        plist_path.as_os_str(),
+    ]);
+
+    let (width, _, atlas) = read_png(&fixture("bgra8888.png"));
+    let at = |x: u32, y: u32| -> [u8; 4] {
+        let start = ((y * width + x) * 4) as usize;
+        atlas[start..start + 4].try_into().unwrap()
+    };
+    let inside = |x: u32, y: u32, rect: (u32, u32, u32, u32)| {
+        (rect.0..rect.0 + rect.2).contains(&x) && (rect.1..rect.1 + rect.3).contains(&y)
+    };
+    for (name, expected) in [
+        ("plain", expected_sprite(4, 2, |x, y| Some(at(x, y)))),
+        // Stored turned a quarter clockwise, so the sprite's left column is the
+        // stored top row.
+        (
+            "turned",
+            expected_sprite(3, 5, |x, y| Some(at(2 + 4 - y, 1 + x))),
+        ),
+        (
+            "trimmed",
+            expected_sprite(5, 6, |x, y| {
+                inside(x, y, (1, 2, 2, 3)).then(|| at(4 + x - 1, 4 + y - 2))
+            }),
+        ),
+        // Offset by one to the right and, with y pointing up, one down from the center.
+        (
+            "centered",
+            expected_sprite(6, 4, |x, y| {
+                inside(x, y, (3, 2, 2, 2)).then(|| at(x - 3, 4 + y - 2))
+            }),
+        ),
+    ] {
+        let sprite = read_png(&dir.join("atlas").join(format!("{name}.png")));
+        assert!(sprite == expected, "{name} differs");
+    }
+}
+
+/// Descriptors that can't be parsed fail as malformed, however deeply they nest.
+#[test]
+fn malformed_descriptors_are_rejected() {
+    let dir = scratch_dir("malformed_descriptors_are_rejected");
+    let nested_plist = format!(
+        "<plist>{}</plist>",
+        "<array>".repeat(100_000) + &"</array>".repeat(100_000)
+    );
+    for (name, descriptor) in [
+        ("nested.json", "[".repeat(200_000)),
+        ("nested.plist", nested_plist),
+        (
+            "surrogate.json",
+            r#"[{"name": "\ud83d\u0041", "x": 0, "y": 0, "w": 1, "h": 1}]"#.to_string(),
+        ),
+    ] {
+        let descriptor_path = dir.join(name);
+        std::fs::write(&descriptor_path, descriptor).unwrap();
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_siltex"))
+            .arg("tex2png")
+            .arg(fixture("bgra8888.tex"))
+            .arg("-o")
+            .arg(dir.join("atlas.png"))
+            .arg("--atlas")
+            .arg(&descriptor_path)
+            .output()
+            .unwrap();
+        assert_eq!(
+            output.status.code(),
+            Some(4),
+            "{name}: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Helpers shared by the integration tests.
+//!
+//! `fixtures/` holds an 8x8 texture for every format, written by `siltex
+//! gen-fixture` with the gradient pattern where the format can be encoded and
+//! the noise pattern otherwise, except for the ETC2 ones, which combine blocks of
+//! every mode by hand. Each comes with a PNG of its known-good RGBA pixels,
+//! produced by reference decoders independent of this crate.
+
+#![allow(dead_code)]
+
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Fixture names of the formats decodable in every build, like `--format` values.
+pub const SIMPLE_FORMATS: [&str; 9] = [
+    "a8", "l8", "la88", "bgr888", "bgra8888", "bgra5551", "bgra4444", "rgba4444", "rgba16",
+];
+pub const PVRTC_FORMATS: [&str; 4] = ["pvrtc2-rgba", "pvrtc4-rgba", "pvrtc2-rgb", "pvrtc4-rgb"];
+pub const ETC_FORMATS: [&str; 2] = ["etc2-rgb", "etc2-rgba"];
+
+/// Formats whose fixtures can be decoded with the features of this build.
+pub fn decodable_formats() -> Vec<&'static str> {
+    let mut formats = SIMPLE_FORMATS.to_vec();
+    if cfg!(feature = "pvrtc") {
+        formats.extend(PVRTC_FORMATS);
+    }
+    if cfg!(feature = "etc") {
+        formats.extend(ETC_FORMATS);
+    }
+    formats
+}
+
+pub fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+/// A single texture with `pixels` in the format with the value `format`.
+pub fn tex(format: u8, width: i16, height: i16, pixels: &[u8]) -> Vec<u8> {
+    let mut tex = b"TEX\n".to_vec();
+    tex.extend_from_slice(&[2, format, 1, 0]);
+    tex.extend_from_slice(&width.to_be_bytes());
+    tex.extend_from_slice(&height.to_be_bytes());
+    // Scale, the pixel data right after the header and no opaque bitmap.
+    for value in [1, 32, pixels.len() as i32, 0, 0] {
+        tex.extend_from_slice(&value.to_be_bytes());
+    }
+    tex.extend_from_slice(pixels);
+    tex
+}
+
+/// A directory of scratch files for the test called `name`, empty at first.
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Reads a PNG with 8-bit samples, returning its layout and pixels.
+pub fn decode_png(path: &Path) -> (png::OutputInfo, Vec<u8>) {
+    let mut decoder = png::Decoder::new(File::open(path).unwrap());
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    pixels.truncate(info.buffer_size());
+    (info, pixels)
+}
+
+/// Reads an RGBA PNG with 8-bit samples, returning its width, height and pixels.
+pub fn read_png(path: &Path) -> (u32, u32, Vec<u8>) {
+    let (info, pixels) = decode_png(path);
+    assert_eq!(
+        info.color_type,
+        png::ColorType::Rgba,
+        "{} isn't RGBA",
+        path.display()
+    );
+    (info.width, info.height, pixels)
+}
+
+/// Runs the `siltex` binary with `args`, panicking if it fails.
+pub fn siltex(args: &[&std::ffi::OsStr]) {
+    let output = Command::new(env!("CARGO_BIN_EXE_siltex"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "siltex {args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// Asserts that every channel of `actual` is within `tolerance` of `expected`.
+pub fn assert_close(actual: &[u8], expected: &[u8], tolerance: u8, what: &str) {
+    assert_eq!(actual.len(), expected.len(), "{what}: sizes differ");
+    for (i, (a, e)) in actual.iter().zip(expected).enumerate() {
+        assert!(
+            a.abs_diff(*e) <= tolerance,
+            "{what}: channel {} of pixel {} is {a} instead of {e}",
+            i % 4,
+            i / 4
+        );
+    }
+}
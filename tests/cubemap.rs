@@ -0,0 +1,73 @@
+//! Writing the faces of cube maps with `tex2png --cubemap`.
+
+mod common;
+
+use common::{read_png, scratch_dir, siltex, tex};
+
+/// A BGRA8888 texture whose pixels have the red of the face they belong to,
+/// counting in the order +x, -x, +y, -y, +z, -z.
+fn cube_texture(width: i16, height: i16, face_at: impl Fn(i16, i16) -> u8) -> Vec<u8> {
+    let pixels = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .flat_map(|(x, y)| [0, 0, face_at(x, y), 0xFF])
+        .collect::<Vec<_>>();
+    tex(0x08, width, height, &pixels)
+}
+
+fn assert_face(path: &std::path::Path, size: u32, face: u8) {
+    let (width, height, rgba) = read_png(path);
+    assert_eq!((width, height), (size, size), "{}", path.display());
+    assert!(
+        rgba.chunks_exact(4)
+            .all(|pixel| pixel == [face, 0, 0, 0xFF]),
+        "{} isn't face {face}",
+        path.display()
+    );
+}
+
+#[test]
+fn faces_are_written_from_either_layout() {
+    let dir = scratch_dir("faces_are_written_from_either_layout");
+    // Column and row of every face of a horizontal cross.
+    let cross = [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)];
+    for (layout, texture) in [
+        ("strip", cube_texture(4, 24, |_, y| (y / 4) as u8)),
+        (
+            "cross",
+            cube_texture(16, 12, |x, y| {
+                let position = cross.iter().position(|&cell| cell == (x / 4, y / 4));
+                position.map_or(0xFF, |face| face as u8)
+            }),
+        ),
+    ] {
+        let tex_path = dir.join(format!("{layout}.tex"));
+        std::fs::write(&tex_path, texture).unwrap();
+        siltex(&[
+            "tex2png".as_ref(),
+            tex_path.as_os_str(),
+            "-o".as_ref(),
+            dir.join(format!("{layout}.png")).as_os_str(),
+            "--cubemap".as_ref(),
+            "--cube-layout".as_ref(),
+            layout.as_ref(),
+        ]);
+        for (face, suffix) in ["posx", "negx", "posy", "negy", "posz", "negz"]
+            .into_iter()
+            .enumerate()
+        {
+            assert_face(&dir.join(format!("{layout}_{suffix}.png")), 4, face as u8);
+        }
+    }
+
+    let face_path = dir.join("negy.png");
+    siltex(&[
+        "tex2png".as_ref(),
+        dir.join("strip.tex").as_os_str(),
+        "-o".as_ref(),
+        face_path.as_os_str(),
+        "--cubemap".as_ref(),
+        "--face".as_ref(),
+        "-y".as_ref(),
+    ]);
+    assert_face(&face_path, 4, 3);
+}
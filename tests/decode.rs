@@ -0,0 +1,299 @@
+//! Decoding the fixtures of every format through the library and `tex2png`.
+
+mod common;
+
+use common::{
+    decodable_formats, decode_png, fixture, read_png, scratch_dir, siltex, ETC_FORMATS,
+    PVRTC_FORMATS, SIMPLE_FORMATS,
+};
+
+#[test]
+fn decode_tex_matches_reference() {
+    for format in decodable_formats() {
+        let tex = std::fs::read(fixture(&format!("{format}.tex"))).unwrap();
+        let image = siltex::decode_tex(&tex).unwrap();
+        let (width, height, expected) = read_png(&fixture(&format!("{format}.png")));
+        assert_eq!((image.width, image.height), (width, height), "{format}");
+        assert!(image.as_rgba_bytes() == expected, "{format} pixels differ");
+    }
+}
+
+#[test]
+fn decode_tex_from_reader_matches_decode_tex() {
+    for format in decodable_formats() {
+        let tex = std::fs::read(fixture(&format!("{format}.tex"))).unwrap();
+        let from_reader = siltex::decode_tex_from_reader(&tex[..]).unwrap();
+        assert_eq!(from_reader, siltex::decode_tex(&tex).unwrap(), "{format}");
+    }
+}
+
+#[test]
+fn tex2png_matches_reference() {
+    let dir = scratch_dir("tex2png_matches_reference");
+    for format in decodable_formats() {
+        let png_path = dir.join(format!("{format}.png"));
+        siltex(&[
+            "tex2png".as_ref(),
+            fixture(&format!("{format}.tex")).as_os_str(),
+            "-o".as_ref(),
+            png_path.as_os_str(),
+            "--expand-rgba".as_ref(),
+        ]);
+        assert!(
+            read_png(&png_path) == read_png(&fixture(&format!("{format}.png"))),
+            "{format} pixels differ"
+        );
+    }
+}
+
+/// Single-channel and luminance-alpha textures are written without the channels
+/// they don't have unless expanded to RGBA.
+#[test]
+fn single_channel_formats_are_written_as_grayscale() {
+    let dir = scratch_dir("single_channel_formats_are_written_as_grayscale");
+    for (format, color_type) in [
+        ("a8", png::ColorType::GrayscaleAlpha),
+        ("l8", png::ColorType::Grayscale),
+        ("la88", png::ColorType::GrayscaleAlpha),
+    ] {
+        let png_path = dir.join(format!("{format}.png"));
+        siltex(&[
+            "tex2png".as_ref(),
+            fixture(&format!("{format}.tex")).as_os_str(),
+            "-o".as_ref(),
+            png_path.as_os_str(),
+        ]);
+        let (info, pixels) = decode_png(&png_path);
+        assert_eq!(info.color_type, color_type, "{format}");
+
+        // Both carry the intensity first and alpha last.
+        let (_, _, expected) = read_png(&fixture(&format!("{format}.png")));
+        let channels = color_type.samples();
+        for (pixel, expected) in pixels.chunks_exact(channels).zip(expected.chunks_exact(4)) {
+            assert_eq!(pixel[0], expected[0], "{format}");
+            if channels == 2 {
+                assert_eq!(pixel[1], expected[3], "{format}");
+            }
+        }
+    }
+}
+
+#[test]
+fn bgr_is_written_as_rgb() {
+    let dir = scratch_dir("bgr_is_written_as_rgb");
+    let png_path = dir.join("bgr888.png");
+    siltex(&[
+        "tex2png".as_ref(),
+        fixture("bgr888.tex").as_os_str(),
+        "-o".as_ref(),
+        png_path.as_os_str(),
+    ]);
+    let (info, pixels) = decode_png(&png_path);
+    assert_eq!(info.color_type, png::ColorType::Rgb);
+    let (_, _, expected) = read_png(&fixture("bgr888.png"));
+    let expected_rgb = expected
+        .chunks_exact(4)
+        .flat_map(|pixel| &pixel[..3])
+        .copied()
+        .collect::<Vec<_>>();
+    assert!(pixels == expected_rgb, "pixels differ");
+}
+
+#[test]
+fn truncated_pixels_are_rejected() {
+    let tex = std::fs::read(fixture("bgra8888.tex")).unwrap();
+    let error = siltex::decode_tex(&tex[..tex.len() - 1]).unwrap_err();
+    assert!(matches!(error, siltex::TexError::Truncated(_)), "{error:?}");
+}
+
+/// The pixel region of textures with mipmaps holds every level after the base one.
+#[test]
+fn mipmapped_textures_decode_their_base_level() {
+    let dir = scratch_dir("mipmapped_textures_decode_their_base_level");
+    let tex_path = dir.join("mipmapped.tex");
+    let png_path = dir.join("mipmapped.png");
+    siltex(&[
+        "gen-fixture".as_ref(),
+        tex_path.as_os_str(),
+        "--width=8".as_ref(),
+        "--height=8".as_ref(),
+        "--mips=4".as_ref(),
+    ]);
+    let image = siltex::decode_tex(&std::fs::read(&tex_path).unwrap()).unwrap();
+    assert!(image == siltex::decode_tex(&std::fs::read(fixture("bgra8888.tex")).unwrap()).unwrap());
+
+    // The mipmaps aren't unexpected data, which would fail under --strict.
+    siltex(&[
+        "tex2png".as_ref(),
+        tex_path.as_os_str(),
+        "-o".as_ref(),
+        png_path.as_os_str(),
+        "--strict".as_ref(),
+    ]);
+    assert!(read_png(&png_path) == read_png(&fixture("bgra8888.png")));
+}
+
+/// Large textures decoded in bands on several threads come out the same as when
+/// decoded on one.
+#[test]
+fn parallel_decode_matches_serial_decode() {
+    let dir = scratch_dir("parallel_decode_matches_serial_decode");
+    let mut formats = vec!["bgra8888", "bgra5551", "rgba16"];
+    if cfg!(feature = "etc") {
+        formats.push("etc2-rgba");
+    }
+    for format in formats {
+        let tex_path = dir.join(format!("{format}.tex"));
+        // A height that isn't a multiple of the bands or blocks.
+        siltex(&[
+            "gen-fixture".as_ref(),
+            tex_path.as_os_str(),
+            format!("--format={format}").as_ref(),
+            "--width=600".as_ref(),
+            "--height=609".as_ref(),
+            "--pattern=noise".as_ref(),
+        ]);
+        let [serial, parallel] = ["1", "3"].map(|threads| {
+            let png_path = dir.join(format!("{format}_{threads}.png"));
+            siltex(&[
+                "tex2png".as_ref(),
+                tex_path.as_os_str(),
+                "-o".as_ref(),
+                png_path.as_os_str(),
+                "--threads".as_ref(),
+                threads.as_ref(),
+            ]);
+            decode_png(&png_path)
+        });
+        assert_eq!(parallel.0.color_type, serial.0.color_type, "{format}");
+        assert!(parallel.1 == serial.1, "{format} pixels differ");
+    }
+}
+
+#[test]
+fn decode_stream_matches_reference() {
+    let dir = scratch_dir("decode_stream_matches_reference");
+    for format in decodable_formats() {
+        if format.starts_with("pvrtc") {
+            continue;
+        }
+        let png_path = dir.join(format!("{format}.png"));
+        let tex = std::fs::File::open(fixture(&format!("{format}.tex"))).unwrap();
+        let png = std::io::BufWriter::new(std::fs::File::create(&png_path).unwrap());
+        let (width, height) = siltex::decode_stream(tex, png).unwrap();
+        let expected = read_png(&fixture(&format!("{format}.png")));
+        assert_eq!((width, height), (expected.0, expected.1), "{format}");
+        assert!(read_png(&png_path) == expected, "{format} pixels differ");
+    }
+
+    let pvrtc = std::fs::File::open(fixture("pvrtc4-rgba.tex")).unwrap();
+    let error = siltex::decode_stream(pvrtc, std::io::sink()).unwrap_err();
+    assert!(
+        matches!(
+            error,
+            siltex::DecodeError::Tex(siltex::TexError::Unsupported(_))
+        ),
+        "{error:?}"
+    );
+}
+
+/// Every PNG written, including indexed and animated ones, reads back as encoded.
+#[test]
+fn written_pngs_compare_equal_when_read_back() {
+    let dir = scratch_dir("written_pngs_compare_equal_when_read_back");
+    let tex_path = dir.join("mipmapped.tex");
+    siltex(&[
+        "gen-fixture".as_ref(),
+        tex_path.as_os_str(),
+        "--width=16".as_ref(),
+        "--height=16".as_ref(),
+        "--mips=5".as_ref(),
+    ]);
+    for (name, options) in [
+        ("plain", &[][..] as &[&str]),
+        ("indexed", &["--palette=16"][..]),
+        ("wide", &["--depth=16"][..]),
+        ("animated", &["--mipmaps", "--apng"][..]),
+    ] {
+        let png_path = dir.join(format!("{name}.png"));
+        let mut args: Vec<&std::ffi::OsStr> = vec![
+            "tex2png".as_ref(),
+            tex_path.as_os_str(),
+            "-o".as_ref(),
+            png_path.as_os_str(),
+            "--compare-png".as_ref(),
+        ];
+        args.extend(options.iter().map(std::ffi::OsStr::new));
+        siltex(&args);
+    }
+}
+
+#[test]
+fn srgb_tags_without_changing_pixels() {
+    let dir = scratch_dir("srgb_tags_without_changing_pixels");
+    let png_path = dir.join("srgb.png");
+    siltex(&[
+        "tex2png".as_ref(),
+        fixture("bgra8888.tex").as_os_str(),
+        "-o".as_ref(),
+        png_path.as_os_str(),
+        "--srgb".as_ref(),
+    ]);
+    let decoder = png::Decoder::new(std::fs::File::open(&png_path).unwrap());
+    let reader = decoder.read_info().unwrap();
+    let info = reader.info();
+    assert!(info.srgb.is_some());
+    assert_eq!(
+        info.source_gamma.map(|gamma| gamma.into_scaled()),
+        Some(45455)
+    );
+    assert!(info.source_chromaticities.is_some());
+    assert!(read_png(&png_path) == read_png(&fixture("bgra8888.png")));
+}
+
+/// Reading inputs ahead of their conversion doesn't change what gets written.
+#[test]
+fn read_ahead_matches_converting_in_turn() {
+    let dir = scratch_dir("read_ahead_matches_converting_in_turn");
+    let inputs = dir.join("in");
+    std::fs::create_dir(&inputs).unwrap();
+    for format in decodable_formats() {
+        let name = format!("{format}.tex");
+        std::fs::copy(fixture(&name), inputs.join(name)).unwrap();
+    }
+    for (output, options) in [
+        ("serial", &[][..] as &[&str]),
+        ("read_ahead", &["--queue-depth=2"][..]),
+        ("parallel", &["--queue-depth=2", "--threads=3"][..]),
+    ] {
+        let output = dir.join(output);
+        let mut args: Vec<&std::ffi::OsStr> = vec![
+            "tex2png".as_ref(),
+            inputs.as_os_str(),
+            "-o".as_ref(),
+            output.as_os_str(),
+        ];
+        args.extend(options.iter().map(std::ffi::OsStr::new));
+        siltex(&args);
+    }
+    for format in decodable_formats() {
+        let name = format!("{format}.png");
+        let serial = std::fs::read(dir.join("serial").join(&name)).unwrap();
+        for output in ["read_ahead", "parallel"] {
+            let written = std::fs::read(dir.join(output).join(&name)).unwrap();
+            assert!(written == serial, "{output} {format} differs");
+        }
+    }
+}
+
+/// Every fixture is named after its format, whose value is recorded in the header.
+#[test]
+fn format_names_and_values_match_fixtures() {
+    let fixtures = [&SIMPLE_FORMATS[..], &PVRTC_FORMATS, &ETC_FORMATS].concat();
+    for name in fixtures {
+        let value = std::fs::read(fixture(&format!("{name}.tex"))).unwrap()[5];
+        let format = siltex::TexFormat::from_value(value).unwrap();
+        assert_eq!(format.name(), name);
+        assert_eq!(u8::from(format), value, "{name}");
+    }
+}
@@ -0,0 +1,111 @@
+//! Exhaustive checks of the formats packing a pixel into 16 bits against a
+//! straightforward floating point expansion of their channels.
+//!
+//! Channels are scaled to 8 bits rounding down, so 5-bit 3 becomes 24 rather
+//! than the nearer 25.
+
+mod common;
+
+use common::{read_png, scratch_dir, siltex, tex};
+
+/// Expands a channel of `bits` bits to 8 bits.
+fn expand(value: u16, bits: u32) -> u8 {
+    let max = ((1 << bits) - 1) as f64;
+    ((value & max as u16) as f64 * 255.0 / max).floor() as u8
+}
+
+fn bgra5551(value: u16) -> [u8; 4] {
+    [
+        expand(value >> 10, 5),
+        expand(value >> 5, 5),
+        expand(value, 5),
+        expand(value >> 15, 1),
+    ]
+}
+
+fn bgra4444(value: u16) -> [u8; 4] {
+    [
+        expand(value >> 8, 4),
+        expand(value >> 4, 4),
+        expand(value, 4),
+        expand(value >> 12, 4),
+    ]
+}
+
+fn rgba4444(value: u16) -> [u8; 4] {
+    [
+        expand(value >> 12, 4),
+        expand(value >> 8, 4),
+        expand(value >> 4, 4),
+        expand(value, 4),
+    ]
+}
+
+/// Expands a packed pixel to RGBA.
+type Reference = fn(u16) -> [u8; 4];
+
+const FORMATS: [(&str, u8, Reference); 3] = [
+    ("bgra5551", 0x0A, bgra5551),
+    ("bgra4444", 0x0B, bgra4444),
+    ("rgba4444", 0x0C, rgba4444),
+];
+
+/// A 256x256 texture holding every 16-bit value once, in increasing order.
+fn every_value(format: u8, to_bytes: fn(u16) -> [u8; 2]) -> Vec<u8> {
+    let pixels = (0..=u16::MAX).flat_map(to_bytes).collect::<Vec<_>>();
+    tex(format, 256, 256, &pixels)
+}
+
+fn assert_matches_reference(rgba: &[u8], reference: Reference, name: &str) {
+    assert_eq!(rgba.len(), 0x10000 * 4, "{name}");
+    for (value, pixel) in (0..=u16::MAX).zip(rgba.chunks_exact(4)) {
+        assert_eq!(pixel, reference(value), "{name} value 0x{value:04X}");
+    }
+}
+
+#[test]
+fn every_little_endian_value_matches_reference() {
+    for (name, format, reference) in FORMATS {
+        let image = siltex::decode_tex(&every_value(format, u16::to_le_bytes)).unwrap();
+        assert_matches_reference(image.as_rgba_bytes(), reference, name);
+    }
+}
+
+#[test]
+fn every_big_endian_value_matches_reference() {
+    let dir = scratch_dir("every_big_endian_value_matches_reference");
+    for (name, format, reference) in FORMATS {
+        let tex_path = dir.join(format!("{name}.tex"));
+        let png_path = dir.join(format!("{name}.png"));
+        std::fs::write(&tex_path, every_value(format, u16::to_be_bytes)).unwrap();
+        siltex(&[
+            "tex2png".as_ref(),
+            tex_path.as_os_str(),
+            "-o".as_ref(),
+            png_path.as_os_str(),
+            "--pixel-endian".as_ref(),
+            "big".as_ref(),
+        ]);
+        assert_matches_reference(&read_png(&png_path).2, reference, name);
+    }
+}
+
+/// Every 4-bit channel value is expanded by repeating it in both nibbles, so
+/// 0x0 becomes 0x00 and 0xF becomes 0xFF.
+#[test]
+fn every_nibble_expands_by_replication() {
+    // Pixel n stores n in all four channels.
+    let pixels = (0..16u16)
+        .flat_map(|nibble| (nibble * 0x1111).to_le_bytes())
+        .collect::<Vec<_>>();
+    for format in [0x0B, 0x0C] {
+        let image = siltex::decode_tex(&tex(format, 16, 1, &pixels)).unwrap();
+        for nibble in 0..16u8 {
+            assert_eq!(
+                image.pixel(nibble as u32, 0),
+                [nibble * 17; 4],
+                "format 0x{format:02X} nibble 0x{nibble:X}"
+            );
+        }
+    }
+}
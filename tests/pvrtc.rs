@@ -0,0 +1,59 @@
+//! Hand-computed PVRTC blocks checking both the 2bpp and the 4bpp modulation.
+//!
+//! Every block of these textures is the same, so the bilinear upscaling of the
+//! colors gives the colors of a single block everywhere: opaque red for color A
+//! and opaque blue for color B, which both widen from 5 bits to exactly 0xFF.
+
+#![cfg(feature = "pvrtc")]
+
+mod common;
+
+use common::tex;
+
+/// Color A opaque red, color B opaque blue, with the mode bit in bit 0.
+const COLORS: u32 = 0x801F_FC00;
+
+const RED: [u8; 4] = [0xFF, 0, 0, 0xFF];
+const BLUE: [u8; 4] = [0, 0, 0xFF, 0xFF];
+
+/// Decodes a `width` by `height` texture in `format` made of 2x2 copies of the
+/// block with `modulation` and the colors in `colors`.
+fn decode_blocks(format: u8, width: i16, height: i16, modulation: u32, colors: u32) -> Vec<u8> {
+    let block = [modulation.to_le_bytes(), colors.to_le_bytes()].concat();
+    let tex = tex(format, width, height, &block.repeat(4));
+    siltex::decode_tex(&tex).unwrap().rgba
+}
+
+fn assert_pixels(rgba: &[u8], expected: impl Fn(usize) -> [u8; 4], what: &str) {
+    for (i, pixel) in rgba.chunks_exact(4).enumerate() {
+        assert_eq!(pixel, expected(i), "{what}: pixel {i}");
+    }
+}
+
+#[test]
+fn four_bit_modulation_blends_colors() {
+    for (modulation, expected, what) in [
+        (0x0000_0000, RED, "only color A"),
+        (0x5555_5555, [0x9F, 0, 0x5F, 0xFF], "3/8 of color B"),
+        (0xAAAA_AAAA, [0x5F, 0, 0x9F, 0xFF], "5/8 of color B"),
+        (0xFFFF_FFFF, BLUE, "only color B"),
+    ] {
+        let rgba = decode_blocks(0x85, 8, 8, modulation, COLORS);
+        assert_pixels(&rgba, |_| expected, what);
+    }
+
+    // With the mode bit set, value 2 is half of either color and transparent.
+    let rgba = decode_blocks(0x85, 8, 8, 0xAAAA_AAAA, COLORS | 1);
+    assert_pixels(&rgba, |_| [0x7F, 0, 0x7F, 0], "punch-through");
+}
+
+#[test]
+fn two_bit_modulation_selects_colors() {
+    // A bit per pixel selects color B for every other column.
+    let rgba = decode_blocks(0x84, 16, 8, 0xAAAA_AAAA, COLORS);
+    assert_pixels(
+        &rgba,
+        |i| if i % 2 == 0 { RED } else { BLUE },
+        "alternating",
+    );
+}
@@ -0,0 +1,128 @@
+//! Converting PNGs to textures with `png2tex` and back.
+
+mod common;
+
+use common::{assert_close, fixture, read_png, scratch_dir, siltex};
+
+/// Converts `png` to a texture in `format` and decodes it again.
+fn round_trip(png: &str, format: &str, test: &str) -> Vec<u8> {
+    let dir = scratch_dir(test);
+    let tex_path = dir.join(format!("{format}.tex"));
+    siltex(&[
+        "png2tex".as_ref(),
+        fixture(png).as_os_str(),
+        "-o".as_ref(),
+        tex_path.as_os_str(),
+        "--format".as_ref(),
+        format.as_ref(),
+    ]);
+    let image = siltex::decode_tex(&std::fs::read(tex_path).unwrap()).unwrap();
+    image.rgba
+}
+
+/// The reference pixels of each encodable format are exactly representable in
+/// it, so they survive a round trip unchanged.
+#[test]
+fn reference_pixels_round_trip_exactly() {
+    for format in [
+        "a8", "l8", "la88", "bgr888", "bgra8888", "bgra5551", "bgra4444", "rgba4444",
+    ] {
+        let png = format!("{format}.png");
+        let (_, _, expected) = read_png(&fixture(&png));
+        let actual = round_trip(&png, format, &format!("exact_{format}"));
+        assert!(actual == expected, "{format} pixels differ");
+    }
+}
+
+/// Formats with fewer bits per channel round each channel to the nearest value
+/// they can store.
+#[test]
+fn gradient_round_trips_within_quantization() {
+    let (_, _, gradient) = read_png(&fixture("bgra8888.png"));
+    for (format, tolerance) in [
+        ("bgra4444", 0xFF / 15 / 2 + 1),
+        ("rgba4444", 0xFF / 15 / 2 + 1),
+    ] {
+        let actual = round_trip("bgra8888.png", format, &format!("lossy_{format}"));
+        assert_close(&actual, &gradient, tolerance, format);
+    }
+
+    // BGRA5551 keeps a single bit of alpha, set for alpha of at least 0x80.
+    let actual = round_trip("bgra8888.png", "bgra5551", "lossy_bgra5551");
+    let expected_alpha = gradient
+        .chunks_exact(4)
+        .map(|pixel| if pixel[3] >= 0x80 { 0xFF } else { 0 });
+    for (i, (pixel, alpha)) in actual.chunks_exact(4).zip(expected_alpha).enumerate() {
+        assert_eq!(pixel[3], alpha, "alpha of pixel {i}");
+    }
+    let rgb = |rgba: &[u8]| -> Vec<u8> {
+        rgba.chunks_exact(4)
+            .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 0])
+            .collect()
+    };
+    assert_close(
+        &rgb(&actual),
+        &rgb(&gradient),
+        0xFF / 31 / 2 + 1,
+        "bgra5551",
+    );
+}
+
+/// Dithering keeps the average color of every part of a shallow gradient close
+/// to the original, where rounding every pixel alone leaves bands.
+#[test]
+fn dithering_smooths_gradients() {
+    let dir = scratch_dir("dithering_smooths_gradients");
+    let (width, height) = (256, 16);
+    // Every value is repeated for four columns, so each 5-bit level spans eight.
+    let gradient = (0..height)
+        .flat_map(|_| (0..width).flat_map(|x| [(x / 4) as u8; 3].into_iter().chain([0xFF])))
+        .collect::<Vec<_>>();
+    let png_path = dir.join("gradient.png");
+    let mut encoder = png::Encoder::new(std::fs::File::create(&png_path).unwrap(), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder
+        .write_header()
+        .unwrap()
+        .write_image_data(&gradient)
+        .unwrap();
+
+    let [plain, dithered] = [&[][..] as &[&str], &["--dither"]].map(|options| {
+        let tex_path = dir.join(if options.is_empty() {
+            "plain.tex"
+        } else {
+            "dithered.tex"
+        });
+        let mut args: Vec<&std::ffi::OsStr> = vec![
+            "png2tex".as_ref(),
+            png_path.as_os_str(),
+            "-o".as_ref(),
+            tex_path.as_os_str(),
+            "--format=bgra5551".as_ref(),
+        ];
+        args.extend(options.iter().map(std::ffi::OsStr::new));
+        siltex(&args);
+        siltex::decode_tex(&std::fs::read(tex_path).unwrap()).unwrap()
+    });
+    assert!(plain != dithered);
+
+    // Mean distance of the average red of every 16 columns from the original.
+    let block_error = |image: &siltex::TexImage| {
+        let blocks = (0..width / 16).map(|block| {
+            let (mut actual, mut expected) = (0, 0);
+            for y in 0..height {
+                for x in block * 16..block * 16 + 16 {
+                    actual += image.pixel(x, y)[0] as u32;
+                    expected += x / 4;
+                }
+            }
+            actual.abs_diff(expected) as f64 / (16 * height) as f64
+        });
+        blocks.sum::<f64>() / (width / 16) as f64
+    };
+    let (plain_error, dithered_error) = (block_error(&plain), block_error(&dithered));
+    assert!(
+        dithered_error * 2.0 < plain_error,
+        "dithered error {dithered_error} isn't well below {plain_error}"
+    );
+}
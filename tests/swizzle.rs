@@ -0,0 +1,56 @@
+//! The named channel permutations on two pixels with distinct channels.
+
+use siltex::swizzle;
+
+/// Two pixels with the bytes 1 to 8, plus a partial pixel that stays untouched.
+const PIXELS: [u8; 10] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+
+fn permuted(permutation: fn(&mut [u8])) -> [u8; 10] {
+    let mut pixels = PIXELS;
+    permutation(&mut pixels);
+    pixels
+}
+
+#[test]
+fn identity_keeps_pixels() {
+    assert_eq!(permuted(swizzle::identity), PIXELS);
+}
+
+#[test]
+fn bgra_to_rgba_swaps_red_and_blue() {
+    assert_eq!(
+        permuted(swizzle::bgra_to_rgba),
+        [3, 2, 1, 4, 7, 6, 5, 8, 9, 10]
+    );
+    assert_eq!(
+        permuted(swizzle::rgba_to_bgra),
+        permuted(swizzle::bgra_to_rgba)
+    );
+}
+
+#[test]
+fn argb_to_rgba_moves_alpha_last() {
+    assert_eq!(
+        permuted(swizzle::argb_to_rgba),
+        [2, 3, 4, 1, 6, 7, 8, 5, 9, 10]
+    );
+}
+
+#[test]
+fn rgba_to_argb_moves_alpha_first() {
+    assert_eq!(
+        permuted(swizzle::rgba_to_argb),
+        [4, 1, 2, 3, 8, 5, 6, 7, 9, 10]
+    );
+    let mut pixels = permuted(swizzle::rgba_to_argb);
+    swizzle::argb_to_rgba(&mut pixels);
+    assert_eq!(pixels, PIXELS);
+}
+
+#[test]
+fn abgr_to_rgba_reverses_channels() {
+    assert_eq!(
+        permuted(swizzle::abgr_to_rgba),
+        [4, 3, 2, 1, 8, 7, 6, 5, 9, 10]
+    );
+}
@@ -0,0 +1,22 @@
+mod common;
+
+use common::{fixture, scratch_dir, siltex};
+
+#[test]
+fn output_template_expands_placeholders() {
+    let dir = scratch_dir("output_template_expands_placeholders");
+    siltex(&[
+        "tex2png".as_ref(),
+        fixture("bgra5551.tex").as_ref(),
+        "-o".as_ref(),
+        dir.as_os_str(),
+        "--output-template".as_ref(),
+        "{stem}.{ext}_{width}x{height}_{format}_{{{index}}}.png".as_ref(),
+    ]);
+
+    let names = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["bgra5551.tex_8x8_bgra5551_{0}.png"]);
+}